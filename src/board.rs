@@ -1,9 +1,31 @@
-use std::fmt;
+use std::collections::HashSet;
+use std::env;
+use std::error::Error;
+use std::fmt::{self, Write};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::iter;
 use std::ops::Deref;
+use std::path::Path;
+
+#[cfg(feature = "serde")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::player::Token;
+use crate::solver::{Outcome, Score, Solver};
+
+pub mod popout;
 
+/// A set of cells of a board, as a bit for each cell.
+///
+/// With the `wide` feature, bitboards have 128 bits rather than 64, so boards can have up to
+/// `W * (H + 1) = 128` cells, such as 9x9 or 11x10 boards, at the cost of slower searches.
+#[cfg(not(feature = "wide"))]
 pub type BitBoard = u64;
+/// A set of cells of a board, as a bit for each cell, with 128 bits for the `wide` feature.
+#[cfg(feature = "wide")]
+pub type BitBoard = u128;
 pub type Column = u8;
 pub type ColumnDiff = i8;
 
@@ -16,42 +38,314 @@ pub type ColumnDiff = i8;
 //  1  8 15 22 29 36 43
 //  0  7 14 21 28 35 42  BOTTOM
 
-// WIDTH * (HEIGHT + 1) must not be larger than the number of bits in a BitBoard.
-// ie. 7 * (6 + 1) = 49 < 64
+// The dimensions of the standard board, and the number of tokens in a row needed to win.
 pub const WIDTH: Column = 7;
 pub const HEIGHT: Column = 6;
 pub const BOARD_SIZE: Column = WIDTH * HEIGHT;
+pub const CONNECT: Column = 4;
+
+// The most moves that can be played on any supported board size.
+const MAX_MOVES: usize = BitBoard::BITS as usize;
+
+// Random keys for a token of each player in each cell, used to make the Zobrist key of a board.
+// The keys are generated with SplitMix64 from a fixed seed, so keys are stable between runs.
+const ZOBRIST: [[u64; MAX_MOVES]; 2] = {
+    let mut keys = [[0; MAX_MOVES]; 2];
+    let mut state: u64 = 0x0123_4567_89ab_cdef;
+    let mut i = 0;
+    while i < 2 * MAX_MOVES {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        keys[i / MAX_MOVES][i % MAX_MOVES] = z ^ (z >> 31);
+        i += 1;
+    }
+    keys
+};
 
-const BOTTOM: BitBoard = ((1 << ((HEIGHT + 1) * WIDTH)) - 1) / ((1 << (HEIGHT + 1)) - 1);
-const TOP: BitBoard = BOTTOM << HEIGHT;
+/// The standard 7x6 connect four board.
+pub type Board = GenericBoard<{ WIDTH as usize }, { HEIGHT as usize }, { CONNECT as usize }>;
 
+/// A board with `W` columns and `H` rows, where `N` tokens in a row win.
 #[derive(Clone, Copy)]
-pub struct Board {
-    moves: [Column; BOARD_SIZE as usize],
-    heights: [Column; WIDTH as usize],
+pub struct GenericBoard<const W: usize, const H: usize, const N: usize = { CONNECT as usize }> {
+    moves: [Column; MAX_MOVES],
+    heights: [Column; W],
     pub players: [BitBoard; 2],
     ply: usize,
+    // The Zobrist keys of the tokens on the board and of the mirrored board, updated with each
+    // move.
+    key: u64,
+    mirror_key: u64,
 }
 
-impl Board {
+impl<const W: usize, const H: usize, const N: usize> GenericBoard<W, H, N> {
+    // W * (H + 1) must not be larger than the number of bits in a BitBoard.
+    // ie. 7 * (6 + 1) = 49 < 64, or 9 * (9 + 1) = 90 < 128 with the `wide` feature
+    const VALID_SIZE: () = assert!(
+        W > 0 && H > 0 && N > 0 && W * (H + 1) <= BitBoard::BITS as usize,
+        "board does not fit in a bitboard"
+    );
+
+    const BOTTOM: BitBoard = {
+        let mut bottom = 0;
+        let mut i = 0;
+        while i < W {
+            bottom |= 1 << (i * (H + 1));
+            i += 1;
+        }
+        bottom
+    };
+    const TOP: BitBoard = Self::BOTTOM << H;
+    const FULL: BitBoard = Self::BOTTOM * ((1 << H) - 1);
+    // Rows 1, 3, 5, ... counting from 1 at the bottom.
+    const ODD_ROWS: BitBoard = Self::BOTTOM * (0x5555_5555_5555_5555 & ((1 << H) - 1));
+
+    // Columns ordered from the center outwards, since central moves tend to be the strongest.
+    // ie. 3, 2, 4, 1, 5, 0, 6
+    const MOVE_ORDER: [Column; W] = {
+        let mut order = [0; W];
+        let mut i = 0;
+        while i < W {
+            order[i] = (if i % 2 == 0 {
+                W / 2 + i / 2
+            } else {
+                W / 2 - i / 2 - 1
+            }) as Column;
+            i += 1;
+        }
+        order
+    };
+
     /// Creates a new empty board.
-    pub const fn new() -> Board {
-        let moves = [0; BOARD_SIZE as usize];
+    pub const fn new() -> GenericBoard<W, H, N> {
+        let () = Self::VALID_SIZE;
+
+        let moves = [0; MAX_MOVES];
         let players = [0; 2];
 
-        let mut heights = [0; WIDTH as usize];
+        let mut heights = [0; W];
         let mut i = 0;
-        while i < WIDTH {
-            heights[i as usize] = (HEIGHT + 1) * i;
+        while i < W {
+            heights[i] = ((H + 1) * i) as Column;
             i += 1;
         }
 
-        Board {
+        GenericBoard {
             moves,
             heights,
             players,
             ply: 0,
+            key: 0,
+            mirror_key: 0,
+        }
+    }
+
+    /// Creates a board by playing the given moves in order.
+    pub fn from_moves(moves: &[Column]) -> Result<GenericBoard<W, H, N>, IllegalMoveError> {
+        let mut board = GenericBoard::new();
+
+        for (index, &column) in moves.iter().enumerate() {
+            if !board.is_legal(column) {
+                return Err(IllegalMoveError { index, column });
+            }
+            board.make_move(column);
+        }
+
+        Ok(board)
+    }
+
+    /// Creates a board from a string of moves, where each move is a digit of the column counting
+    /// from 1.
+    ///
+    /// ie. `"4453"` plays in the 4th, 4th, 5th and 3rd columns.
+    pub fn from_move_string(moves: &str) -> Result<GenericBoard<W, H, N>, MoveStringError> {
+        let mut board = GenericBoard::new();
+
+        for (index, c) in moves.chars().enumerate() {
+            let column = match c.to_digit(10) {
+                Some(digit) if digit > 0 => (digit - 1) as Column,
+                _ => return Err(MoveStringError::InvalidChar { index, c }),
+            };
+
+            if !board.is_legal(column) {
+                return Err(MoveStringError::IllegalMove(IllegalMoveError {
+                    index,
+                    column,
+                }));
+            }
+            board.make_move(column);
+        }
+
+        Ok(board)
+    }
+
+    /// Gets the moves played so far as a string, in the format of [`GenericBoard::from_move_string`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if a move was played in a column after the 9th, which cannot be written as a digit.
+    pub fn to_move_string(&self) -> String {
+        self.move_history()
+            .iter()
+            .map(|&column| {
+                std::char::from_digit(u32::from(column) + 1, 10)
+                    .expect("column cannot be written as a digit")
+            })
+            .collect()
+    }
+
+    /// Gets the moves played so far in the notation of the standard Connect 4 test sets, which
+    /// is the same as [`GenericBoard::to_move_string`].
+    pub fn to_notation(&self) -> String {
+        self.to_move_string()
+    }
+
+    /// Creates a board from moves in the notation of the standard Connect 4 test sets, which is
+    /// the same as [`GenericBoard::from_move_string`].
+    pub fn from_notation(moves: &str) -> Result<GenericBoard<W, H, N>, NotationError> {
+        GenericBoard::from_move_string(moves)
+    }
+
+    /// Saves the moves played on the board to a file, which can be loaded with
+    /// [`GenericBoard::load_from_path`].
+    ///
+    /// The file has a line for each field of the game, of the name of the field and its value
+    /// separated by `:`, where the moves are in the format of
+    /// [`GenericBoard::to_move_string`].
+    ///
+    /// ```text
+    /// moves: 4453
+    /// ```
+    pub fn save_to_path(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, format!("moves: {}\n", self.to_move_string()))
+    }
+
+    /// Loads a board from a file saved with [`GenericBoard::save_to_path`] or
+    /// [`Game::save_to_path`], replaying the moves that were saved.
+    ///
+    /// Fields other than the moves are ignored.
+    ///
+    /// [`Game::save_to_path`]: crate::game::Game::save_to_path
+    pub fn load_from_path(path: &Path) -> Result<GenericBoard<W, H, N>, LoadError> {
+        let contents = fs::read_to_string(path)?;
+
+        let moves = contents
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.trim() == "moves")
+            .map(|(_, moves)| moves.trim())
+            .ok_or(LoadError::MissingMoves)?;
+
+        Ok(GenericBoard::from_move_string(moves)?)
+    }
+
+    /// Creates a board from a grid of tokens, in the format written by the `Display`
+    /// implementation of the board.
+    ///
+    /// There is a line for each row from the top, of the symbols of the tokens in each cell
+    /// separated by spaces, where empty cells are `.`. The lines after the rows, from the one
+    /// starting with `-` below the board, are ignored. Since the grid only holds the position,
+    /// the moves of the board are played in an order that reaches it, which may not be the order
+    /// in which they were played.
+    pub fn from_grid(grid: &str) -> Result<GenericBoard<W, H, N>, GridError> {
+        let lines: Vec<&str> = grid
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .take_while(|line| !line.starts_with('-'))
+            .collect();
+        if lines.len() != H {
+            return Err(GridError::RowCount(lines.len()));
+        }
+
+        // The tokens of each column, from the bottom up.
+        let mut columns = vec![Vec::with_capacity(H); W];
+        for (i, line) in lines.iter().enumerate().rev() {
+            let row = H - 1 - i;
+
+            let cells: Vec<&str> = line.split_whitespace().collect();
+            if cells.len() != W {
+                return Err(GridError::RowLength {
+                    row,
+                    len: cells.len(),
+                });
+            }
+
+            for (column, &cell) in cells.iter().enumerate() {
+                if cell == "." {
+                    continue;
+                }
+
+                let token = cell.parse().map_err(|_| GridError::InvalidSymbol {
+                    row,
+                    column,
+                    symbol: cell.to_owned(),
+                })?;
+                if columns[column].len() != row {
+                    return Err(GridError::FloatingToken { row, column });
+                }
+                columns[column].push(token);
+            }
+        }
+
+        let count = |token: Token| -> usize {
+            let count = |column: &Vec<Token>| column.iter().filter(|&&t| t == token).count();
+            columns.iter().map(count).sum()
+        };
+        let (player1, player2) = (count(Token::Player1), count(Token::Player2));
+        if player1 != player2 && player1 != player2 + 1 {
+            return Err(GridError::TokenCount { player1, player2 });
         }
+
+        let mut heights: Vec<usize> = columns.iter().map(Vec::len).collect();
+        let mut moves = Vec::with_capacity(player1 + player2);
+        if !unplay(&columns, &mut heights, &mut moves, &mut HashSet::new()) {
+            return Err(GridError::Unreachable);
+        }
+
+        let mut board = GenericBoard::new();
+        for &column in moves.iter().rev() {
+            board.make_move(column);
+        }
+        Ok(board)
+    }
+
+    /// Gets the columns of the moves played so far, in the order they were played.
+    pub fn move_history(&self) -> &[Column] {
+        &self.moves[..self.ply]
+    }
+
+    /// Gets the number of moves played so far.
+    pub fn ply(&self) -> usize {
+        self.ply
+    }
+
+    /// Gets the bitboard of the cells holding a token of either player.
+    pub fn occupied(&self) -> BitBoard {
+        self.players[0] | self.players[1]
+    }
+
+    /// Gets the bitboard of the cells holding a token of the given player.
+    pub fn player_board(&self, token: Token) -> BitBoard {
+        self.players[token.player() as usize - 1]
+    }
+
+    /// Gets the number of tokens on the board, which is the number of moves played.
+    pub fn piece_count(&self) -> usize {
+        self.occupied().count_ones() as usize
+    }
+
+    /// Gets the board reflected across its center column.
+    ///
+    /// The mirrored board is equivalent to the original, since connect four is symmetric.
+    pub fn mirror(&self) -> GenericBoard<W, H, N> {
+        let mut board = GenericBoard::new();
+        for &column in self.move_history() {
+            board.make_move(Self::reflect_column(column, true));
+        }
+        board
     }
 
     /// Gets the token of the current player.
@@ -66,7 +360,7 @@ impl Board {
 
     /// Gets the token in the given row and column if not empty.
     pub fn token_at(&self, row: Column, column: Column) -> Option<Token> {
-        let mask = 1 << (row + (column * (HEIGHT + 1)));
+        let mask = 1 << (row as usize + (column as usize * (H + 1)));
 
         if (self.players[0] & mask) != 0 {
             Some(Token::Player1)
@@ -80,22 +374,71 @@ impl Board {
     /// Gets whether the given column has space.
     pub fn has_space(&self, column: Column) -> bool {
         assert!(
-            column < WIDTH,
+            (column as usize) < W,
             "column out of range [0, {}): {}",
-            WIDTH,
+            W,
             column
         );
 
         Self::is_legal_board(self.players[self.ply & 1] | (1 << self.heights[column as usize]))
     }
 
+    /// Gets the number of tokens in the given column, in the range `[0, H]`.
+    pub fn column_height(&self, column: Column) -> Column {
+        assert!(
+            (column as usize) < W,
+            "column out of range [0, {}): {}",
+            W,
+            column
+        );
+
+        self.heights[column as usize] - ((H + 1) * column as usize) as Column
+    }
+
+    /// Gets the number of tokens in each column, like [`GenericBoard::column_height`].
+    pub fn column_heights(&self) -> [Column; W] {
+        let mut heights = self.heights;
+        for (column, height) in heights.iter_mut().enumerate() {
+            *height -= ((H + 1) * column) as Column;
+        }
+        heights
+    }
+
+    /// Gets the cells holding tokens, as the row, the column and the token in the cell.
+    ///
+    /// The cells are in order of column, and from the bottom of each column up.
+    pub fn cells(&self) -> impl Iterator<Item = (Column, Column, Token)> {
+        let player1 = self.players[0];
+        let mut occupied = self.occupied();
+
+        iter::from_fn(move || {
+            if occupied == 0 {
+                return None;
+            }
+
+            let cell = occupied.trailing_zeros() as usize;
+            occupied &= occupied - 1;
+
+            let token = if (player1 & (1 << cell)) != 0 {
+                Token::Player1
+            } else {
+                Token::Player2
+            };
+            Some((
+                (cell % (H + 1)) as Column,
+                (cell / (H + 1)) as Column,
+                token,
+            ))
+        })
+    }
+
     /// Gets whether a move in the given column is legal.
     pub fn is_legal(&self, column: Column) -> bool {
-        column < WIDTH && self.has_space(column)
+        (column as usize) < W && self.has_space(column)
     }
 
     /// Peeks ahead at the board state following a move in the given column.
-    pub fn peekable(&mut self, column: Column) -> PeekableBoard {
+    pub fn peekable(&mut self, column: Column) -> PeekableBoard<'_, W, H, N> {
         self.make_move(column);
         PeekableBoard { board: self }
     }
@@ -105,6 +448,7 @@ impl Board {
         assert!(self.has_space(column), "column is full: {}", column);
 
         self.players[self.ply & 1] ^= 1 << self.heights[column as usize];
+        self.toggle_keys(column);
         self.heights[column as usize] += 1;
 
         self.moves[self.ply] = column;
@@ -118,6 +462,18 @@ impl Board {
 
         self.heights[column as usize] -= 1;
         self.players[self.ply & 1] ^= 1 << self.heights[column as usize];
+        self.toggle_keys(column);
+    }
+
+    /// Toggles the token of the current player in the top of the given column in the Zobrist
+    /// keys.
+    fn toggle_keys(&mut self, column: Column) {
+        let column = column as usize;
+        let cell = self.heights[column] as usize;
+        let mirror_cell = cell - column * (H + 1) + (W - 1 - column) * (H + 1);
+
+        self.key ^= ZOBRIST[self.ply & 1][cell];
+        self.mirror_key ^= ZOBRIST[self.ply & 1][mirror_cell];
     }
 
     /// Gets the token of the winner, if there is one.
@@ -134,122 +490,1486 @@ impl Board {
         }
     }
 
+    /// Gets the result of the game on the board.
+    pub fn result(&self) -> GameResult {
+        match self.winner() {
+            Some(winner) => GameResult::Win(winner),
+            None if self.is_full() => GameResult::Draw,
+            None => GameResult::InProgress,
+        }
+    }
+
+    /// Gets whether every cell of the board has a token in it.
+    pub fn is_full(&self) -> bool {
+        self.occupied() == Self::FULL
+    }
+
+    /// Gets whether the game ended in a draw, with the board full and no winner.
+    pub fn is_draw(&self) -> bool {
+        self.is_full() && self.winner().is_none()
+    }
+
+    /// Gets a column in which the current player would win immediately, if there is one.
+    pub fn winning_move(&self) -> Option<Column> {
+        let board = self.players[self.ply & 1];
+
+        self.legal_moves()
+            .find(|&column| Self::is_win(board | (1 << self.heights[column as usize])))
+    }
+
+    /// Gets a bitboard of the empty cells that would complete a line of `N` for the given token.
+    ///
+    /// This includes cells that cannot be played into yet.
+    pub fn threats(&self, token: Token) -> BitBoard {
+        let board = self.player_board(token);
+        let occupied = self.occupied();
+
+        Self::winning_cells(board) & (Self::FULL ^ occupied)
+    }
+
+    /// Gets an iterator of the columns in which the given token would complete a line of `N` with
+    /// its next move.
+    pub fn winning_moves(&self, token: Token) -> LegalMoves<H> {
+        LegalMoves {
+            mask: self.threats(token) & self.legal_moves_mask(),
+        }
+    }
+
+    /// Gets a bitboard of the cells the current player could play into to win immediately.
+    pub fn immediate_threats(&self) -> BitBoard {
+        self.threats(self.current_player()) & self.legal_moves_mask()
+    }
+
+    /// Counts the windows of `N` cells in a line that the given token could still complete, by
+    /// the number of its tokens already in them.
+    ///
+    /// Index `k` of the result is the number of windows holding `k` of the tokens and none of the
+    /// opponent's. Completed lines are not counted.
+    pub fn open_windows(&self, token: Token) -> [u32; N] {
+        let board = self.player_board(token);
+        let available = Self::FULL & !self.player_board(token.opponent());
+
+        let mut counts = [0; N];
+
+        // Horizontal, vertical, diagonal \ and diagonal /.
+        for &shift in &[H + 1, 1, H, H + 2] {
+            // Cells at the start of a window free of the opponent's tokens.
+            let mut open = available;
+            // Cells at the start of a window with more than `k` of the tokens at index `k`.
+            let mut more_than = [0; N];
+
+            for j in 0..N {
+                let amount = (j * shift) as isize;
+                let cells = Self::shift(board, amount);
+                open &= Self::shift(available, amount);
+
+                for k in (1..N).rev() {
+                    more_than[k] |= more_than[k - 1] & cells;
+                }
+                more_than[0] |= cells;
+            }
+
+            counts[0] += (open & !more_than[0]).count_ones();
+            for k in 1..N {
+                counts[k] += (open & more_than[k - 1] & !more_than[k]).count_ones();
+            }
+        }
+
+        counts
+    }
+
+    /// Gets the number of threats for the given token in odd and even rows respectively.
+    ///
+    /// Rows are counted from 1 at the bottom. Odd threats tend to be winning for the first player
+    /// and even threats for the second player.
+    pub fn threat_parity(&self, token: Token) -> (u32, u32) {
+        let threats = self.threats(token);
+
+        (
+            (threats & Self::ODD_ROWS).count_ones(),
+            (threats & !Self::ODD_ROWS).count_ones(),
+        )
+    }
+
     /// Gets an iterator of legal moves.
-    pub fn legal_moves(&self) -> LegalMoves {
+    pub fn legal_moves(&self) -> LegalMoves<H> {
         LegalMoves {
-            board: self.players[self.ply & 1],
-            heights: self.heights,
-            column: 0,
+            mask: self.legal_moves_mask(),
+        }
+    }
+
+    /// Gets a bitboard of the cells that can be played into immediately.
+    pub fn legal_moves_mask(&self) -> BitBoard {
+        let occupied = self.occupied();
+
+        // Adding the bottom row carries into the lowest empty cell of each column.
+        (occupied + Self::BOTTOM) & Self::FULL
+    }
+
+    /// Gets an iterator of legal moves, ordered from the center column outwards.
+    pub fn ordered_moves(&self) -> impl Iterator<Item = Column> {
+        let board = self.players[self.ply & 1];
+        let heights = self.heights;
+
+        IntoIterator::into_iter(Self::MOVE_ORDER)
+            .filter(move |&column| Self::is_legal_board(board | (1 << heights[column as usize])))
+    }
+
+    /// Gets the Zobrist key of the board, a hash of the tokens on the board that is updated
+    /// incrementally with each move.
+    ///
+    /// Unlike [`GenericBoard::position_code`], different positions may share a key, although it
+    /// is very unlikely. Modifying `players` directly does not update the key.
+    pub fn zobrist_key(&self) -> u64 {
+        self.key
+    }
+
+    /// Gets the smaller of the Zobrist keys of the board and of its mirror, and whether it is the
+    /// key of the mirror.
+    ///
+    /// A board and its mirror share a canonical key, so they can share an entry in a
+    /// transposition table. Columns stored with the key must be mirrored if it is the key of the
+    /// mirror.
+    pub fn canonical_key(&self) -> (u64, bool) {
+        if self.mirror_key < self.key {
+            (self.mirror_key, true)
+        } else {
+            (self.key, false)
+        }
+    }
+
+    /// Gets the column reflected across the center column if `mirrored` is set, such as a column
+    /// stored with the canonical key of a board that is the key of its mirror.
+    pub fn reflect_column(column: Column, mirrored: bool) -> Column {
+        if mirrored {
+            W as Column - 1 - column
+        } else {
+            column
         }
     }
 
     /// Gets an encoding of the board position state.
     pub fn position_code(&self) -> BitBoard {
-        self.players[self.ply & 1] + self.players[0] + self.players[1] + BOTTOM
+        self.players[self.ply & 1] + self.occupied() + Self::BOTTOM
+    }
+
+    /// Gets whether the boards hold the same tokens with the same player to move, even if the
+    /// moves were played in a different order.
+    ///
+    /// Boards are equal with `==` exactly when this is true, so boards reached by transposing
+    /// moves are equal and hash the same, even though their move histories differ.
+    pub fn same_position(&self, other: &GenericBoard<W, H, N>) -> bool {
+        self.players == other.players && (self.ply & 1) == (other.ply & 1)
     }
 
     /// Gets whether the given board is a winning board.
     const fn is_win(board: BitBoard) -> bool {
-        let mut h = board & (board >> (HEIGHT + 1)); // Horizontal
-        let mut v = board & (board >> 1); // Vertical
-        let mut d1 = board & (board >> HEIGHT); // Diagonal \
-        let mut d2 = board & (board >> (HEIGHT + 2)); // Diagonal /
+        // Horizontal, vertical, diagonal \ and diagonal /.
+        let shifts = [H + 1, 1, H, H + 2];
+        let mut i = 0;
+        while i < shifts.len() {
+            // Cells at the start of a line of N.
+            let mut line = board;
+            let mut j = 1;
+            while j < N {
+                line &= Self::shift(board, (j * shifts[i]) as isize);
+                j += 1;
+            }
+
+            if line != 0 {
+                return true;
+            }
+            i += 1;
+        }
+
+        false
+    }
+
+    /// Gets the cells that would complete a line of `N` on the given board.
+    const fn winning_cells(board: BitBoard) -> BitBoard {
+        let mut cells = 0;
+
+        // Horizontal, vertical, diagonal \ and diagonal /.
+        let shifts = [H + 1, 1, H, H + 2];
+        let mut i = 0;
+        while i < shifts.len() {
+            let shift = shifts[i] as isize;
+
+            // For each position of the gap in the line, check the rest of the line is filled.
+            let mut gap = 0;
+            while gap < N as isize {
+                let mut line = !0;
+                let mut j = -gap;
+                while j < N as isize - gap {
+                    if j != 0 {
+                        line &= Self::shift(board, j * shift);
+                    }
+                    j += 1;
+                }
+
+                cells |= line;
+                gap += 1;
+            }
 
-        h &= h >> 2 * (HEIGHT + 1);
-        v &= v >> 2;
-        d1 &= d1 >> 2 * HEIGHT;
-        d2 &= d2 >> 2 * (HEIGHT + 2);
+            i += 1;
+        }
+
+        cells
+    }
 
-        (h | v | d1 | d2) != 0
+    /// Shifts the bits of the board down by the given amount, or up if it is negative.
+    const fn shift(board: BitBoard, amount: isize) -> BitBoard {
+        if amount.unsigned_abs() >= BitBoard::BITS as usize {
+            0
+        } else if amount >= 0 {
+            board >> amount
+        } else {
+            board << -amount
+        }
     }
 
     /// Gets whether the given board is legal.
     const fn is_legal_board(board: BitBoard) -> bool {
-        (board & TOP) == 0
+        (board & Self::TOP) == 0
     }
 }
 
-impl fmt::Display for Board {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for row in (0..HEIGHT).rev() {
-            for column in 0..WIDTH {
+impl Board {
+    /// Solves the board, returning the exact score for the current player with perfect play.
+    ///
+    /// See [`Solver::solve`] for details on the score, use a [`Solver`] directly to reuse its
+    /// transposition table between positions.
+    pub fn solve(&self) -> Score {
+        Solver::new().solve(self)
+    }
+
+    /// Solves whether the current player wins, draws or loses with perfect play.
+    ///
+    /// This is much faster than [`Board::solve`] when the exact score is not needed.
+    pub fn wdl(&self) -> Outcome {
+        Solver::new().wdl(self)
+    }
+
+    /// Finds the number of plies within `max_plies` in which the current player can force a win,
+    /// counting the winning move, or `None` if they cannot force a win that soon.
+    ///
+    /// See [`Solver::mate_in`] for details.
+    pub fn mate_in(&self, max_plies: usize) -> Option<usize> {
+        Solver::new().mate_in(self, max_plies)
+    }
+}
+
+impl<const W: usize, const H: usize, const N: usize> Default for GenericBoard<W, H, N> {
+    fn default() -> GenericBoard<W, H, N> {
+        GenericBoard::new()
+    }
+}
+
+impl<const W: usize, const H: usize, const N: usize> GenericBoard<W, H, N> {
+    /// Renders the board as text in the given style.
+    pub fn render(&self, style: RenderStyle) -> String {
+        self.render_colored(style, colors_enabled())
+    }
+
+    /// Renders the board as text in the given style, drawing tokens with the given symbols.
+    pub fn render_with(&self, style: RenderStyle, symbols: &TokenSymbols) -> String {
+        self.render_styled(style, symbols, colors_enabled())
+    }
+
+    /// Renders the board in the given style with its default symbols, where `color` is whether
+    /// [`RenderStyle::Color`] uses colors.
+    fn render_colored(&self, style: RenderStyle, color: bool) -> String {
+        let symbols = match style {
+            RenderStyle::Ascii => TokenSymbols::default(),
+            RenderStyle::Unicode => TokenSymbols::DISCS,
+            // The colors tell the players apart, so both are drawn with a filled disc.
+            RenderStyle::Color if color => TokenSymbols {
+                player1: TokenSymbols::DISCS.player1,
+                player2: TokenSymbols::DISCS.player1,
+            },
+            RenderStyle::Color => TokenSymbols::DISCS,
+        };
+        self.render_styled(style, &symbols, color)
+    }
+
+    /// Renders the board in the given style, where `color` is whether [`RenderStyle::Color`]
+    /// uses colors.
+    fn render_styled(&self, style: RenderStyle, symbols: &TokenSymbols, color: bool) -> String {
+        let mut out = String::new();
+
+        // Writing to a string cannot fail.
+        let _ = match style {
+            RenderStyle::Ascii => self.write_ascii(&mut out, symbols),
+            RenderStyle::Unicode => self.write_unicode(&mut out, symbols, false),
+            RenderStyle::Color => self.write_unicode(&mut out, symbols, color),
+        };
+
+        out
+    }
+
+    /// Writes the board in plain ASCII, apart from the symbols.
+    fn write_ascii(&self, out: &mut impl Write, symbols: &TokenSymbols) -> fmt::Result {
+        for row in (0..H).rev() {
+            for column in 0..W {
                 if column > 0 {
-                    f.write_str(" ")?;
+                    out.write_str(" ")?;
                 }
 
-                f.write_str(match self.token_at(row, column) {
-                    Some(token) => token.char(),
+                out.write_str(match self.token_at(row as Column, column as Column) {
+                    Some(token) => symbols.get(token),
                     None => ".",
                 })?;
             }
 
-            f.write_str("\n")?;
+            out.write_str("\n")?;
         }
 
         // Divider below board.
-        const DIVIDER_LEN: usize = ((2 * WIDTH) - 1) as usize;
-        writeln!(f, "{:-<len$}", "", len = DIVIDER_LEN)?;
+        let divider_len = (2 * W) - 1;
+        writeln!(out, "{:-<len$}", "", len = divider_len)?;
 
-        for i in 1..=WIDTH {
+        for i in 1..=W {
             if i > 1 {
-                f.write_str(" ")?;
+                out.write_str(" ")?;
+            }
+            write!(out, "{}", i)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the board in a grid of box-drawing characters, with the tokens of each player in
+    /// red and yellow if `color` is set.
+    fn write_unicode(
+        &self,
+        out: &mut impl Write,
+        symbols: &TokenSymbols,
+        color: bool,
+    ) -> fmt::Result {
+        let border = "\u{2500}".repeat(2 * W + 1);
+
+        writeln!(out, "\u{250c}{}\u{2510}", border)?;
+        for row in (0..H).rev() {
+            out.write_str("\u{2502}")?;
+            for column in 0..W {
+                match self.token_at(row as Column, column as Column) {
+                    Some(token) if color => {
+                        let code = match token {
+                            Token::Player1 => 31,
+                            Token::Player2 => 33,
+                        };
+                        write!(out, " \x1b[{}m{}\x1b[0m", code, symbols.get(token))?;
+                    }
+                    Some(token) => write!(out, " {}", symbols.get(token))?,
+                    None => out.write_str(" \u{00b7}")?,
+                }
             }
-            write!(f, "{}", i)?;
+            out.write_str(" \u{2502}\n")?;
+        }
+        writeln!(out, "\u{2514}{}\u{2518}", border)?;
+
+        out.write_str(" ")?;
+        for i in 1..=W {
+            write!(out, " {}", i)?;
         }
 
         Ok(())
     }
 }
 
-pub struct PeekableBoard<'a> {
-    board: &'a mut Board,
+impl<const W: usize, const H: usize, const N: usize> fmt::Display for GenericBoard<W, H, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_ascii(f, &TokenSymbols::default())
+    }
+}
+
+// The rest of the state of a board follows from its moves, so only the moves are shown.
+impl<const W: usize, const H: usize, const N: usize> fmt::Debug for GenericBoard<W, H, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GenericBoard")
+            .field("moves", &self.move_history())
+            .finish()
+    }
+}
+
+// Boards are equal when they hold the same position, whatever order the moves were played in, so
+// the move history is not compared.
+impl<const W: usize, const H: usize, const N: usize> PartialEq for GenericBoard<W, H, N> {
+    fn eq(&self, other: &GenericBoard<W, H, N>) -> bool {
+        self.same_position(other)
+    }
+}
+
+impl<const W: usize, const H: usize, const N: usize> Eq for GenericBoard<W, H, N> {}
+
+impl<const W: usize, const H: usize, const N: usize> Hash for GenericBoard<W, H, N> {
+    fn hash<S: Hasher>(&self, state: &mut S) {
+        self.players.hash(state);
+        (self.ply & 1).hash(state);
+    }
+}
+
+// Boards are serialized as the sequence of moves played, since the rest of the state can be derived
+// from it.
+#[cfg(feature = "serde")]
+impl<const W: usize, const H: usize, const N: usize> Serialize for GenericBoard<W, H, N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.move_history())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, const W: usize, const H: usize, const N: usize> Deserialize<'de>
+    for GenericBoard<W, H, N>
+{
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<GenericBoard<W, H, N>, D::Error> {
+        let moves = Vec::<Column>::deserialize(deserializer)?;
+
+        GenericBoard::from_moves(&moves).map_err(de::Error::custom)
+    }
+}
+
+/// Gets whether colors may be used when rendering, which they may unless the `NO_COLOR`
+/// environment variable is set.
+fn colors_enabled() -> bool {
+    env::var_os("NO_COLOR").is_none()
+}
+
+/// A style of rendering a board as text.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RenderStyle {
+    /// Plain ASCII, the same as the [`Display`](fmt::Display) of the board.
+    Ascii,
+    /// Discs in a grid of box-drawing characters.
+    Unicode,
+    /// The same as [`RenderStyle::Unicode`] with red and yellow discs drawn with ANSI colors.
+    ///
+    /// Colors are left out if the `NO_COLOR` environment variable is set, in which case the
+    /// discs are drawn as in [`RenderStyle::Unicode`], so callers should only use this style when
+    /// writing to a terminal.
+    Color,
+}
+
+/// The symbols used to draw the tokens of each player when rendering a board.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TokenSymbols<'a> {
+    pub player1: &'a str,
+    pub player2: &'a str,
+}
+
+impl TokenSymbols<'static> {
+    /// Filled and hollow discs, the default symbols for [`RenderStyle::Unicode`].
+    pub const DISCS: TokenSymbols<'static> = TokenSymbols {
+        player1: "\u{25cf}",
+        player2: "\u{25cb}",
+    };
+}
+
+impl<'a> TokenSymbols<'a> {
+    /// Gets the symbol of the given token.
+    pub fn get(&self, token: Token) -> &'a str {
+        match token {
+            Token::Player1 => self.player1,
+            Token::Player2 => self.player2,
+        }
+    }
+}
+
+impl Default for TokenSymbols<'static> {
+    /// Gets the symbols of [`Token::char`].
+    fn default() -> TokenSymbols<'static> {
+        TokenSymbols {
+            player1: Token::Player1.char(),
+            player2: Token::Player2.char(),
+        }
+    }
+}
+
+/// The result of a game.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GameResult {
+    /// The game was won by the player with the token.
+    Win(Token),
+    /// The board was filled without either player winning.
+    Draw,
+    /// The game is not finished.
+    InProgress,
 }
 
-impl<'a> PeekableBoard<'a> {
-    pub fn peek(&mut self, column: Column) -> PeekableBoard {
+impl GameResult {
+    /// Gets the token of the winner, if there is one.
+    pub fn winner(self) -> Option<Token> {
+        match self {
+            GameResult::Win(winner) => Some(winner),
+            GameResult::Draw | GameResult::InProgress => None,
+        }
+    }
+
+    /// Gets whether the game is finished, either by a win or a draw.
+    pub fn is_over(self) -> bool {
+        self != GameResult::InProgress
+    }
+}
+
+/// An error from playing an illegal move.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct IllegalMoveError {
+    /// The index of the move in the sequence of moves.
+    pub index: usize,
+    /// The column of the move.
+    pub column: Column,
+}
+
+impl fmt::Display for IllegalMoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "illegal move at index {}: column {}",
+            self.index, self.column
+        )
+    }
+}
+
+impl Error for IllegalMoveError {}
+
+/// An error from parsing a string of moves.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MoveStringError {
+    /// A character that is not a column from 1 to 9.
+    InvalidChar { index: usize, c: char },
+    /// A move that could not be played.
+    IllegalMove(IllegalMoveError),
+}
+
+impl fmt::Display for MoveStringError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveStringError::InvalidChar { index, c } => {
+                write!(f, "invalid column at index {}: {:?}", index, c)
+            }
+            MoveStringError::IllegalMove(err) => err.fmt(f),
+        }
+    }
+}
+
+impl Error for MoveStringError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MoveStringError::InvalidChar { .. } => None,
+            MoveStringError::IllegalMove(err) => Some(err),
+        }
+    }
+}
+
+/// An error from parsing moves in the notation of the standard Connect 4 test sets.
+pub type NotationError = MoveStringError;
+
+/// An error from loading a saved board.
+#[derive(Debug)]
+pub enum LoadError {
+    /// An error reading the file.
+    Io(io::Error),
+    /// A file without the moves of the board.
+    MissingMoves,
+    /// Moves that could not be played.
+    InvalidMoves(MoveStringError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(err) => err.fmt(f),
+            LoadError::MissingMoves => f.write_str("missing moves"),
+            LoadError::InvalidMoves(err) => err.fmt(f),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadError::Io(err) => Some(err),
+            LoadError::MissingMoves => None,
+            LoadError::InvalidMoves(err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> LoadError {
+        LoadError::Io(err)
+    }
+}
+
+impl From<MoveStringError> for LoadError {
+    fn from(err: MoveStringError) -> LoadError {
+        LoadError::InvalidMoves(err)
+    }
+}
+
+/// Takes back the moves of a position, from the last move to the first, pushing the column of each
+/// move to `moves`.
+///
+/// `columns` holds the tokens of each column from the bottom up, and `heights` the number of
+/// tokens in each column that have not been taken back yet. Returns whether all the moves could be
+/// taken back, with the players taking turns and the first player moving first. States that were
+/// found not to lead back to the empty board are added to `failed`.
+fn unplay(
+    columns: &[Vec<Token>],
+    heights: &mut Vec<usize>,
+    moves: &mut Vec<Column>,
+    failed: &mut HashSet<Vec<usize>>,
+) -> bool {
+    let remaining: usize = heights.iter().sum();
+    if remaining == 0 {
+        return true;
+    }
+    if failed.contains(heights) {
+        return false;
+    }
+
+    // The first player makes the moves with even indices.
+    let token = if remaining % 2 == 1 {
+        Token::Player1
+    } else {
+        Token::Player2
+    };
+
+    for column in 0..columns.len() {
+        let height = heights[column];
+        if height == 0 || columns[column][height - 1] != token {
+            continue;
+        }
+
+        heights[column] -= 1;
+        moves.push(column as Column);
+        if unplay(columns, heights, moves, failed) {
+            return true;
+        }
+        moves.pop();
+        heights[column] += 1;
+    }
+
+    failed.insert(heights.clone());
+    false
+}
+
+/// An error from parsing a grid of tokens.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum GridError {
+    /// A grid with the wrong number of rows.
+    RowCount(usize),
+    /// A row with the wrong number of cells.
+    RowLength { row: usize, len: usize },
+    /// A cell that is neither empty nor the symbol of a token.
+    InvalidSymbol {
+        row: usize,
+        column: usize,
+        symbol: String,
+    },
+    /// A token above an empty cell.
+    FloatingToken { row: usize, column: usize },
+    /// Tokens that could not have been played in turn, since the first player must have as many
+    /// tokens as the second player or one more.
+    TokenCount { player1: usize, player2: usize },
+    /// A position that cannot be reached with the players taking turns.
+    Unreachable,
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GridError::RowCount(rows) => write!(f, "wrong number of rows: {}", rows),
+            GridError::RowLength { row, len } => {
+                write!(f, "wrong number of cells in row {}: {}", row, len)
+            }
+            GridError::InvalidSymbol {
+                row,
+                column,
+                symbol,
+            } => write!(
+                f,
+                "invalid symbol at row {}, column {}: {:?}",
+                row, column, symbol
+            ),
+            GridError::FloatingToken { row, column } => write!(
+                f,
+                "token above an empty cell at row {}, column {}",
+                row, column
+            ),
+            GridError::TokenCount { player1, player2 } => write!(
+                f,
+                "player 1 has {} tokens and player 2 has {}",
+                player1, player2
+            ),
+            GridError::Unreachable => f.write_str("position cannot be reached"),
+        }
+    }
+}
+
+impl Error for GridError {}
+
+pub struct PeekableBoard<
+    'a,
+    const W: usize = { WIDTH as usize },
+    const H: usize = { HEIGHT as usize },
+    const N: usize = { CONNECT as usize },
+> {
+    board: &'a mut GenericBoard<W, H, N>,
+}
+
+impl<'a, const W: usize, const H: usize, const N: usize> PeekableBoard<'a, W, H, N> {
+    pub fn peek(&mut self, column: Column) -> PeekableBoard<'_, W, H, N> {
         self.board.make_move(column);
 
         PeekableBoard { board: self.board }
     }
 }
 
-impl<'a> Drop for PeekableBoard<'a> {
+impl<'a, const W: usize, const H: usize, const N: usize> Drop for PeekableBoard<'a, W, H, N> {
     fn drop(&mut self) {
         self.board.undo_move();
     }
 }
 
-impl<'a> Deref for PeekableBoard<'a> {
-    type Target = Board;
+impl<'a, const W: usize, const H: usize, const N: usize> Deref for PeekableBoard<'a, W, H, N> {
+    type Target = GenericBoard<W, H, N>;
 
     fn deref(&self) -> &Self::Target {
         self.board
     }
 }
 
-pub struct LegalMoves {
-    board: BitBoard,
-    heights: [Column; WIDTH as usize],
-    column: Column,
+pub struct LegalMoves<const H: usize = { HEIGHT as usize }> {
+    mask: BitBoard,
 }
 
-impl Iterator for LegalMoves {
+impl<const H: usize> Iterator for LegalMoves<H> {
     type Item = Column;
 
     fn next(&mut self) -> Option<Self::Item> {
-        while self.column < WIDTH {
-            let column = self.column;
-            self.column += 1;
+        if self.mask == 0 {
+            return None;
+        }
+
+        let cell = self.mask.trailing_zeros() as usize;
+        self.mask &= self.mask - 1;
+
+        Some((cell / (H + 1)) as Column)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.mask.count_ones() as usize;
+        (len, Some(len))
+    }
+}
+
+impl<const H: usize> DoubleEndedIterator for LegalMoves<H> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.mask == 0 {
+            return None;
+        }
+
+        let cell = (BitBoard::BITS - 1 - self.mask.leading_zeros()) as usize;
+        self.mask ^= 1 << cell;
+
+        Some((cell / (H + 1)) as Column)
+    }
+}
+
+impl<const H: usize> ExactSizeIterator for LegalMoves<H> {}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::DefaultHasher;
+    use std::process;
+
+    use super::*;
+
+    /// Gets the hash of the board.
+    fn hash_of(board: &Board) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        board.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn transposed_boards_are_equal() {
+        let board = Board::from_move_string("1234").unwrap();
+        let transposed = Board::from_move_string("3214").unwrap();
+
+        assert!(board.same_position(&transposed));
+        assert_eq!(board, transposed);
+        assert_eq!(hash_of(&board), hash_of(&transposed));
+        assert_ne!(board.move_history(), transposed.move_history());
+
+        let positions: HashSet<Board> = vec![board, transposed].into_iter().collect();
+        assert_eq!(positions.len(), 1);
+    }
+
+    #[test]
+    fn different_positions_are_not_equal() {
+        let board = Board::from_move_string("1234").unwrap();
+        let swapped = Board::from_move_string("1243").unwrap();
+
+        assert!(!board.same_position(&swapped));
+        assert_ne!(board, swapped);
+        assert_ne!(board, Board::from_move_string("123").unwrap());
+    }
+
+    #[test]
+    fn debug_shows_the_moves() {
+        let board = Board::from_move_string("4453").unwrap();
+
+        assert_eq!(
+            format!("{:?}", board),
+            "GenericBoard { moves: [3, 3, 4, 2] }"
+        );
+    }
+
+    #[test]
+    fn column_height_counts_the_tokens_in_the_column() {
+        let board = Board::from_move_string("4453111111").unwrap();
+
+        assert_eq!(board.column_height(0), HEIGHT);
+        assert_eq!(board.column_height(2), 1);
+        assert_eq!(board.column_height(3), 2);
+        assert_eq!(board.column_height(6), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "column out of range [0, 7): 7")]
+    fn column_height_out_of_range_panics() {
+        Board::new().column_height(WIDTH);
+    }
+
+    #[test]
+    fn winning_move_completes_a_line_of_the_current_player() {
+        let winning_move = |moves| Board::from_move_string(moves).unwrap().winning_move();
+
+        // Up a column, along the bottom row and along a diagonal.
+        assert_eq!(winning_move("121212"), Some(0));
+        assert_eq!(winning_move("112233"), Some(3));
+        assert_eq!(winning_move("1223343454"), Some(3));
+
+        assert_eq!(winning_move(""), None);
+        // Only the opponent threatens to win.
+        assert_eq!(winning_move("141576"), None);
+    }
+
+    /// Gets the bitboard of the cell in the given row and column.
+    fn cell(row: usize, column: usize) -> BitBoard {
+        1 << (row + column * (HEIGHT as usize + 1))
+    }
+
+    #[test]
+    fn threats_are_the_cells_that_complete_a_line() {
+        let board = Board::from_move_string("11223").unwrap();
+        assert_eq!(board.threats(Token::Player1), cell(0, 3));
+        assert_eq!(board.threats(Token::Player2), 0);
+
+        let board = Board::from_move_string("121212").unwrap();
+        assert_eq!(board.threats(Token::Player1), cell(3, 0));
+        assert_eq!(board.threats(Token::Player2), cell(3, 1));
+
+        // The diagonal is completed above an empty cell, so it cannot be played into yet.
+        let board = Board::from_move_string("12233434").unwrap();
+        assert_ne!(board.threats(Token::Player1) & cell(3, 3), 0);
+        assert_eq!(board.threats(Token::Player1) & board.occupied(), 0);
+        assert_eq!(board.immediate_threats(), 0);
+    }
+
+    #[test]
+    fn threats_are_counted_by_the_parity_of_their_row() {
+        assert_eq!(Board::new().threat_parity(Token::Player1), (0, 0));
+
+        // The bottom row is row 1.
+        let board = Board::from_move_string("11223").unwrap();
+        assert_eq!(board.threat_parity(Token::Player1), (1, 0));
+        assert_eq!(board.threat_parity(Token::Player2), (0, 0));
+
+        // Threats count even if the cell below is empty, such as above a column in row 4.
+        let board = Board::from_move_string("121212").unwrap();
+        assert_eq!(board.threat_parity(Token::Player1), (0, 1));
+        assert_eq!(board.threat_parity(Token::Player2), (0, 1));
+
+        // Each player can complete a row on either side, in row 1 and row 2 respectively.
+        let board = Board::from_move_string("334455").unwrap();
+        assert_eq!(board.threat_parity(Token::Player1), (2, 0));
+        assert_eq!(board.threat_parity(Token::Player2), (0, 2));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn board_is_serialized_as_its_moves() {
+        use serde_test::{assert_de_tokens_error, assert_tokens, Token};
+
+        let board = Board::from_move_string("4453").unwrap();
+        assert_tokens(
+            &board,
+            &[
+                Token::Seq { len: Some(4) },
+                Token::U8(3),
+                Token::U8(3),
+                Token::U8(4),
+                Token::U8(2),
+                Token::SeqEnd,
+            ],
+        );
+
+        assert_de_tokens_error::<Board>(
+            &[Token::Seq { len: Some(1) }, Token::U8(7), Token::SeqEnd],
+            "illegal move at index 0: column 7",
+        );
+    }
+
+    #[test]
+    fn small_board_is_won_in_rows_and_columns() {
+        type SmallBoard = GenericBoard<5, 4>;
+
+        let board = SmallBoard::from_move_string("1122334").unwrap();
+        assert_eq!(board.winner(), Some(Token::Player1));
+
+        let board = SmallBoard::from_move_string("5454545").unwrap();
+        assert_eq!(board.winner(), Some(Token::Player1));
+        assert!(!board.is_legal(4));
+        assert!(!board.is_legal(5));
+        assert_eq!(board.legal_moves().collect::<Vec<_>>(), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn board_is_made_from_moves() {
+        let board = Board::from_moves(&[3, 3, 4, 2]).unwrap();
+        assert_eq!(board, Board::from_move_string("4453").unwrap());
+        assert_eq!(board.move_history(), [3, 3, 4, 2]);
+
+        assert_eq!(
+            Board::from_moves(&[0, 0, 0, 0, 0, 0, 0]),
+            Err(IllegalMoveError {
+                index: 6,
+                column: 0,
+            })
+        );
+        assert_eq!(
+            Board::from_moves(&[3, WIDTH]),
+            Err(IllegalMoveError {
+                index: 1,
+                column: WIDTH,
+            })
+        );
+        assert_eq!(
+            IllegalMoveError {
+                index: 6,
+                column: 0,
+            }
+            .to_string(),
+            "illegal move at index 6: column 0"
+        );
+    }
+
+    #[test]
+    fn boards_are_won_with_other_lengths_of_line() {
+        type ThreeBoard = GenericBoard<7, 6, 3>;
+        type FiveBoard = GenericBoard<7, 6, 5>;
+        let winner_of = |moves| ThreeBoard::from_move_string(moves).unwrap().winner();
+        assert_eq!(winner_of("1212"), None);
+        assert_eq!(winner_of("12121"), Some(Token::Player1));
+        assert_eq!(winner_of("11223"), Some(Token::Player1));
+
+        let winner_of = |moves| FiveBoard::from_move_string(moves).unwrap().winner();
+        assert_eq!(winner_of("1212121"), None);
+        assert_eq!(winner_of("121212121"), Some(Token::Player1));
+        assert_eq!(winner_of("1122334"), None);
+        assert_eq!(winner_of("112233445"), Some(Token::Player1));
+    }
+
+    #[test]
+    fn legal_moves_mask_holds_the_lowest_empty_cell_of_each_column() {
+        let bottom: BitBoard = (0..WIDTH as usize).map(|column| cell(0, column)).sum();
+        assert_eq!(Board::new().legal_moves_mask(), bottom);
+
+        // The first column is full and the fourth has two tokens.
+        let board = Board::from_move_string("44111111").unwrap();
+        let expected = (bottom & !cell(0, 0) & !cell(0, 3)) | cell(2, 3);
+        assert_eq!(board.legal_moves_mask(), expected);
+        assert_eq!(board.legal_moves_mask() & board.occupied(), 0);
+    }
+
+    #[test]
+    fn move_string_round_trips() {
+        for &moves in &["", "4453", "2252576253462244111563365343671351441"] {
+            let board = Board::from_move_string(moves).unwrap();
+            assert_eq!(board.to_move_string(), moves);
+        }
+
+        let board = GenericBoard::<9, 4>::from_moves(&[8, 0]).unwrap();
+        assert_eq!(board.to_move_string(), "91");
+    }
+
+    #[test]
+    #[should_panic(expected = "column cannot be written as a digit")]
+    fn move_string_of_a_column_after_the_9th_panics() {
+        let board = GenericBoard::<10, 4>::from_moves(&[9]).unwrap();
+        board.to_move_string();
+    }
+
+    #[test]
+    fn board_of_another_size_fills_and_wins() {
+        // The largest board on 64 bit bitboards, with five in a row to win.
+        type LargeBoard = GenericBoard<8, 7, 5>;
+
+        let mut board = LargeBoard::new();
+        assert_eq!(board.legal_moves().count(), 8);
+        for _ in 0..7 {
+            board.make_move(7);
+        }
+        assert!(!board.is_legal(7));
+        assert!(board.is_legal(6));
+        assert_eq!(board.winner(), None);
+
+        let board = LargeBoard::from_move_string("121212121").unwrap();
+        assert_eq!(board.winner(), Some(Token::Player1));
+    }
+
+    #[test]
+    fn legal_moves_can_be_reversed() {
+        let moves: Vec<Column> = Board::new().legal_moves().rev().collect();
+        assert_eq!(moves, [6, 5, 4, 3, 2, 1, 0]);
+
+        // The first column is full.
+        let board = Board::from_move_string("111111").unwrap();
+        let mut moves = board.legal_moves();
+        assert_eq!(moves.len(), 6);
+        assert_eq!(moves.next_back(), Some(6));
+        assert_eq!(moves.next(), Some(1));
+        assert_eq!(moves.len(), 4);
+        assert_eq!(moves.rev().collect::<Vec<_>>(), [5, 4, 3, 2]);
+    }
+
+    #[test]
+    fn ordered_moves_start_from_the_center() {
+        let moves: Vec<Column> = Board::new().ordered_moves().collect();
+        assert_eq!(moves, [3, 2, 4, 1, 5, 0, 6]);
+
+        // The center column is full.
+        let board = Board::from_move_string("444444").unwrap();
+        let moves: Vec<Column> = board.ordered_moves().collect();
+        assert_eq!(moves, [2, 4, 1, 5, 0, 6]);
+
+        let moves: Vec<Column> = GenericBoard::<8, 4>::new().ordered_moves().collect();
+        assert_eq!(moves, [4, 3, 5, 2, 6, 1, 7, 0]);
+    }
+
+    #[test]
+    fn threats_and_diagonal_wins_use_the_length_of_line() {
+        type ThreeBoard = GenericBoard<7, 6, 3>;
+        type FiveBoard = GenericBoard<7, 6, 5>;
+
+        let board = ThreeBoard::from_move_string("121").unwrap();
+        assert_eq!(board.winning_moves(Token::Player1).collect::<Vec<_>>(), [0]);
+        let board = ThreeBoard::from_move_string("1223733").unwrap();
+        assert_eq!(board.winner(), Some(Token::Player1));
+
+        let board = FiveBoard::from_move_string("1212121").unwrap();
+        assert_eq!(board.winning_moves(Token::Player1).collect::<Vec<_>>(), [0]);
+        let board = FiveBoard::from_move_string("112233").unwrap();
+        assert_eq!(board.winning_move(), None);
+        let board = FiveBoard::from_move_string("11223344").unwrap();
+        assert_eq!(board.winning_move(), Some(4));
+    }
+
+    #[test]
+    fn move_history_follows_moves_and_undos() {
+        let mut board = Board::new();
+        assert!(board.move_history().is_empty());
+
+        board.make_move(3);
+        board.make_move(3);
+        board.make_move(4);
+        assert_eq!(board.move_history(), [3, 3, 4]);
+
+        board.undo_move();
+        board.make_move(2);
+        assert_eq!(board.move_history(), [3, 3, 2]);
+        assert_eq!(board.ply(), 3);
+
+        // Peeking at a move leaves the history as it was.
+        assert_eq!(board.peekable(5).move_history(), [3, 3, 2, 5]);
+        assert_eq!(board.move_history(), [3, 3, 2]);
+    }
+
+    #[test]
+    fn winning_moves_are_found_for_either_player() {
+        let winning_moves = |moves, token| -> Vec<Column> {
+            let board = Board::from_move_string(moves).unwrap();
+            board.winning_moves(token).collect()
+        };
+
+        // The second player is to move, but only the first can win, at either end of the row.
+        assert_eq!(winning_moves("44553", Token::Player1), [1, 5]);
+        assert!(winning_moves("44553", Token::Player2).is_empty());
 
-            if Board::is_legal_board(self.board | (1 << self.heights[column as usize])) {
-                return Some(column);
+        assert_eq!(winning_moves("121212", Token::Player1), [0]);
+        assert_eq!(winning_moves("121212", Token::Player2), [1]);
+
+        // The threat on the diagonal cannot be played into yet.
+        assert!(!winning_moves("12233434", Token::Player1).contains(&3));
+    }
+
+    /// Computes the Zobrist key of the board from the tokens on it.
+    fn key_from_scratch(board: &Board) -> u64 {
+        let mut key = 0;
+        for (player, &tokens) in board.players.iter().enumerate() {
+            for (cell, &cell_key) in ZOBRIST[player].iter().enumerate() {
+                if tokens & (1 << cell) != 0 {
+                    key ^= cell_key;
+                }
             }
         }
+        key
+    }
+
+    #[test]
+    fn incremental_key_matches_the_tokens() {
+        let mut board = Board::new();
+        for &column in &[3, 3, 4, 2, 0, 6, 6, 5, 1, 3] {
+            board.make_move(column);
+            assert_eq!(board.zobrist_key(), key_from_scratch(&board));
+        }
+
+        while board.ply() > 0 {
+            board.undo_move();
+            assert_eq!(board.zobrist_key(), key_from_scratch(&board));
+        }
+        assert_eq!(board.zobrist_key(), 0);
+    }
 
-        None
+    #[test]
+    fn peeking_restores_the_key() {
+        let mut board = Board::from_move_string("4453").unwrap();
+        let key = board.zobrist_key();
+
+        for column in 0..WIDTH {
+            let peeked = board.peekable(column);
+            assert_eq!(peeked.zobrist_key(), key_from_scratch(&peeked));
+        }
+        assert_eq!(board.zobrist_key(), key);
     }
 
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        (0, Some(WIDTH as usize))
+    #[test]
+    fn full_board_without_a_winner_is_a_draw() {
+        let mut board =
+            Board::from_move_string("112224243125463563541273141564336756677757").unwrap();
+        assert!(board.is_full());
+        assert!(board.is_draw());
+        assert_eq!(board.result(), GameResult::Draw);
+
+        board.undo_move();
+        assert!(!board.is_full());
+        assert!(!board.is_draw());
+
+        let won = Board::from_move_string("1212121").unwrap();
+        assert!(!won.is_full());
+        assert!(!won.is_draw());
+        assert!(!Board::new().is_full());
+    }
+
+    #[test]
+    fn mirroring_twice_gives_the_original() {
+        for &moves in &[
+            "",
+            "4453",
+            "1234567",
+            "2252576253462244111563365343671351441",
+        ] {
+            let board = Board::from_move_string(moves).unwrap();
+            let mirror = board.mirror();
+
+            assert_eq!(mirror.mirror(), board, "{}", moves);
+            assert_eq!(mirror.mirror().move_history(), board.move_history());
+            assert_eq!(mirror.winner(), board.winner());
+        }
+
+        let mirror = Board::from_move_string("4453").unwrap().mirror();
+        assert_eq!(mirror.to_move_string(), "4435");
+        assert_ne!(mirror, Board::from_move_string("4453").unwrap());
+    }
+
+    #[test]
+    fn result_follows_the_board() {
+        let result = |moves| Board::from_move_string(moves).unwrap().result();
+
+        assert_eq!(result(""), GameResult::InProgress);
+        assert_eq!(result("1212121"), GameResult::Win(Token::Player1));
+        assert_eq!(result("71212121"), GameResult::Win(Token::Player2));
+        assert_eq!(
+            result("112224243125463563541273141564336756677757"),
+            GameResult::Draw
+        );
+
+        assert_eq!(
+            GameResult::Win(Token::Player2).winner(),
+            Some(Token::Player2)
+        );
+        assert_eq!(GameResult::Draw.winner(), None);
+        assert_eq!(GameResult::InProgress.winner(), None);
+        assert!(GameResult::Win(Token::Player1).is_over());
+        assert!(GameResult::Draw.is_over());
+        assert!(!GameResult::InProgress.is_over());
+    }
+
+    #[test]
+    fn ascii_rendering() {
+        let board = Board::from_move_string("4453").unwrap();
+
+        let expected = "\
+. . . . . . .
+. . . . . . .
+. . . . . . .
+. . . . . . .
+. . . o . . .
+. . o x x . .
+-------------
+1 2 3 4 5 6 7";
+        assert_eq!(board.render(RenderStyle::Ascii), expected);
+        assert_eq!(board.to_string(), expected);
+    }
+
+    #[test]
+    fn unicode_rendering() {
+        let board = Board::from_move_string("4453").unwrap();
+
+        let expected = "\
+┌───────────────┐
+│ · · · · · · · │
+│ · · · · · · · │
+│ · · · · · · · │
+│ · · · · · · · │
+│ · · · ○ · · · │
+│ · · ○ ● ● · · │
+└───────────────┘
+  1 2 3 4 5 6 7";
+        assert_eq!(board.render(RenderStyle::Unicode), expected);
+    }
+
+    #[test]
+    fn color_rendering() {
+        let board = Board::from_move_string("4453").unwrap();
+
+        let (x, o) = ("\x1b[31m●\x1b[0m", "\x1b[33m●\x1b[0m");
+        let expected = format!(
+            "\
+┌───────────────┐
+│ · · · · · · · │
+│ · · · · · · · │
+│ · · · · · · · │
+│ · · · · · · · │
+│ · · · {o} · · · │
+│ · · {o} {x} {x} · · │
+└───────────────┘
+  1 2 3 4 5 6 7",
+            x = x,
+            o = o
+        );
+        assert_eq!(board.render_colored(RenderStyle::Color, true), expected);
+    }
+
+    #[test]
+    fn color_rendering_without_colors_tells_players_apart() {
+        let board = Board::from_move_string("4453").unwrap();
+
+        assert_eq!(
+            board.render_colored(RenderStyle::Color, false),
+            board.render(RenderStyle::Unicode)
+        );
+    }
+
+    #[test]
+    fn rendering_with_custom_symbols() {
+        let board = Board::from_move_string("4453").unwrap();
+        let symbols = TokenSymbols {
+            player1: "R",
+            player2: "Y",
+        };
+
+        let expected = "\
+. . . . . . .
+. . . . . . .
+. . . . . . .
+. . . . . . .
+. . . Y . . .
+. . Y R R . .
+-------------
+1 2 3 4 5 6 7";
+        assert_eq!(board.render_with(RenderStyle::Ascii, &symbols), expected);
+
+        let expected = "\
+┌───────────────┐
+│ · · · · · · · │
+│ · · · · · · · │
+│ · · · · · · · │
+│ · · · · · · · │
+│ · · · Y · · · │
+│ · · Y R R · · │
+└───────────────┘
+  1 2 3 4 5 6 7";
+        assert_eq!(board.render_with(RenderStyle::Unicode, &symbols), expected);
+
+        // The symbols are kept when drawn in color.
+        let colored = board.render_styled(RenderStyle::Color, &symbols, true);
+        assert!(colored.contains("\x1b[31mR\x1b[0m"));
+        assert!(colored.contains("\x1b[33mY\x1b[0m"));
+    }
+
+    #[test]
+    fn player_boards_hold_the_tokens_of_each_player() {
+        let board = Board::from_move_string("4453").unwrap();
+        let player1 = cell(0, 3) | cell(0, 4);
+        let player2 = cell(1, 3) | cell(0, 2);
+
+        assert_eq!(board.player_board(Token::Player1), player1);
+        assert_eq!(board.player_board(Token::Player2), player2);
+        assert_eq!(board.occupied(), player1 | player2);
+        assert_eq!(board.piece_count(), 4);
+
+        assert_eq!(Board::new().occupied(), 0);
+    }
+
+    #[test]
+    fn grid_round_trips() {
+        for &moves in &["", "4453", "2252576253462244111563365343671351441"] {
+            let board = Board::from_move_string(moves).unwrap();
+            assert_eq!(Board::from_grid(&board.to_string()), Ok(board), "{}", moves);
+        }
+    }
+
+    #[test]
+    fn impossible_grids_are_rejected() {
+        let grid = |bottom: &str| format!("{}{}", ". . . . . . .\n".repeat(5), bottom);
+
+        assert_eq!(
+            Board::from_grid(". . . . . . ."),
+            Err(GridError::RowCount(1))
+        );
+        assert_eq!(
+            Board::from_grid(&grid("x . . . . .")),
+            Err(GridError::RowLength { row: 0, len: 6 })
+        );
+        assert_eq!(
+            Board::from_grid(&grid("x . . r . . .")),
+            Err(GridError::InvalidSymbol {
+                row: 0,
+                column: 3,
+                symbol: "r".to_owned(),
+            })
+        );
+        assert_eq!(
+            Board::from_grid(&grid("o . . . . . .")),
+            Err(GridError::TokenCount {
+                player1: 0,
+                player2: 1,
+            })
+        );
+
+        let floating = ". . . . . . .\n".repeat(4) + ". x . . . . .\no . . . . . .";
+        assert_eq!(
+            Board::from_grid(&floating),
+            Err(GridError::FloatingToken { row: 1, column: 1 })
+        );
+
+        // The first player must have moved before the second, so their token cannot be on top.
+        let unreachable = ". . . . . . .\n".repeat(4) + "x . . . . . .\no . . . . . .";
+        assert_eq!(Board::from_grid(&unreachable), Err(GridError::Unreachable));
+    }
+
+    #[test]
+    fn saved_board_is_loaded() {
+        let board = Board::from_move_string("4453").unwrap();
+
+        let path = env::temp_dir().join(format!("connect4-board-{}.txt", process::id()));
+        board.save_to_path(&path).unwrap();
+        let loaded = Board::load_from_path(&path);
+        fs::write(&path, "player1: Someone\n").unwrap();
+        let missing = Board::load_from_path(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.unwrap().move_history(), board.move_history());
+        assert!(matches!(missing, Err(LoadError::MissingMoves)));
+    }
+
+    #[test]
+    fn notation_round_trips() {
+        for &moves in &["", "4453", "2252576253462244111563365343671351441"] {
+            let board = Board::from_notation(moves).unwrap();
+            assert_eq!(board.to_notation(), moves);
+            assert_eq!(Board::from_notation(&board.to_notation()).unwrap(), board);
+        }
+
+        assert!(Board::from_notation("4458").is_err());
+    }
+
+    #[cfg(feature = "wide")]
+    #[test]
+    fn wide_board_is_won_beyond_64_bits() {
+        type WideBoard = GenericBoard<9, 9>;
+
+        // The first player wins with the top four cells of the last column.
+        let board = WideBoard::from_move_string("1999999191929").unwrap();
+        assert_eq!(board.winner(), Some(Token::Player1));
+        assert!(!board.is_legal(8));
+        assert_eq!(board.column_height(8), 9);
+
+        let board = WideBoard::from_move_string("199999919192").unwrap();
+        assert_eq!(board.winner(), None);
+        assert_eq!(board.winning_move(), Some(8));
+    }
+
+    #[test]
+    fn mate_in_finds_forced_wins_within_the_limit() {
+        let board = Board::from_move_string("4455").unwrap();
+        assert_eq!(board.mate_in(3), Some(3));
+        assert_eq!(board.mate_in(9), Some(3));
+        assert_eq!(board.mate_in(2), None);
+
+        let board = Board::from_move_string("7422341735647741166133573473242566").unwrap();
+        assert_eq!(board.mate_in(7), Some(7));
+        assert_eq!(board.mate_in(6), None);
+    }
+
+    #[test]
+    fn mate_in_finds_no_win_without_one() {
+        assert_eq!(Board::new().mate_in(7), None);
+
+        // The game is drawn with perfect play.
+        let board = Board::from_move_string("23163416124767223154467471272416755633").unwrap();
+        assert_eq!(board.mate_in(10), None);
+
+        let board = Board::from_move_string("1212121").unwrap();
+        assert_eq!(board.mate_in(5), None);
+    }
+
+    #[test]
+    fn cells_and_column_heights_follow_the_tokens() {
+        let board = Board::from_move_string("4453").unwrap();
+
+        let cells: Vec<(Column, Column, Token)> = board.cells().collect();
+        assert_eq!(
+            cells,
+            [
+                (0, 2, Token::Player2),
+                (0, 3, Token::Player1),
+                (1, 3, Token::Player2),
+                (0, 4, Token::Player1),
+            ]
+        );
+        for (row, column, token) in cells {
+            assert_eq!(board.token_at(row, column), Some(token));
+        }
+        assert_eq!(board.column_heights(), [0, 0, 1, 2, 1, 0, 0]);
+
+        assert_eq!(Board::new().cells().count(), 0);
+        assert_eq!(Board::new().column_heights(), [0; WIDTH as usize]);
     }
 }
@@ -0,0 +1,223 @@
+use std::fmt;
+
+use crate::board::{BitBoard, Column, GenericBoard, CONNECT, HEIGHT, WIDTH};
+use crate::player::Token;
+
+/// A move in the Pop Out variant.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Move {
+    /// Drops a token into the top of the column.
+    Drop(Column),
+    /// Removes one of the current player's tokens from the bottom of the column, moving the rest
+    /// of the column down.
+    Pop(Column),
+}
+
+/// The standard 7x6 board for the Pop Out variant.
+pub type PopOutBoard =
+    GenericPopOutBoard<{ WIDTH as usize }, { HEIGHT as usize }, { CONNECT as usize }>;
+
+/// A board for the Pop Out variant, where a player may pop one of their own tokens out of the
+/// bottom of a column instead of dropping a token.
+///
+/// A pop can complete lines for both players at once, in which case the player who popped wins.
+/// Since a full board still allows pops, the game is only drawn when the current player has no
+/// legal moves. Repeated positions are not detected.
+#[derive(Clone)]
+pub struct GenericPopOutBoard<const W: usize, const H: usize, const N: usize = { CONNECT as usize }>
+{
+    // The `moves` of the board are not kept up to date, since games may be longer than the board
+    // can hold. The history is kept here instead. Neither is the Zobrist key of the board.
+    board: GenericBoard<W, H, N>,
+    history: Vec<Move>,
+}
+
+impl<const W: usize, const H: usize, const N: usize> GenericPopOutBoard<W, H, N> {
+    /// Creates a new empty board.
+    pub fn new() -> GenericPopOutBoard<W, H, N> {
+        GenericPopOutBoard {
+            board: GenericBoard::new(),
+            history: Vec::new(),
+        }
+    }
+
+    /// Gets the token of the current player.
+    pub fn current_player(&self) -> Token {
+        self.board.current_player()
+    }
+
+    /// Gets the token in the given row and column if not empty.
+    pub fn token_at(&self, row: Column, column: Column) -> Option<Token> {
+        self.board.token_at(row, column)
+    }
+
+    /// Gets the moves played so far.
+    pub fn history(&self) -> &[Move] {
+        &self.history
+    }
+
+    /// Gets whether the given move is legal.
+    pub fn is_legal(&self, mv: Move) -> bool {
+        match mv {
+            Move::Drop(column) => self.board.is_legal(column),
+            Move::Pop(column) => {
+                (column as usize) < W
+                    && (self.board.players[self.board.ply & 1] & Self::bottom_cell(column)) != 0
+            }
+        }
+    }
+
+    /// Gets an iterator of legal moves, with all drops before pops.
+    pub fn legal_moves(&self) -> impl Iterator<Item = Move> {
+        let board = self.board.players[self.board.ply & 1];
+
+        let drops = self.board.legal_moves().map(Move::Drop);
+        let pops = (0..W as Column)
+            .filter(move |&column| (board & Self::bottom_cell(column)) != 0)
+            .map(Move::Pop);
+
+        drops.chain(pops)
+    }
+
+    /// Makes a move for the current player.
+    pub fn make_move(&mut self, mv: Move) {
+        assert!(self.is_legal(mv), "illegal move: {:?}", mv);
+
+        let board = &mut self.board;
+        match mv {
+            Move::Drop(column) => {
+                board.players[board.ply & 1] ^= 1 << board.heights[column as usize];
+                board.heights[column as usize] += 1;
+            }
+            Move::Pop(column) => {
+                let mask = Self::column_mask(column);
+                for player in &mut board.players {
+                    *player = (*player & !mask) | (((*player & mask) >> 1) & mask);
+                }
+                board.heights[column as usize] -= 1;
+            }
+        }
+
+        board.ply += 1;
+        self.history.push(mv);
+    }
+
+    /// Undoes the previous move.
+    pub fn undo_move(&mut self) {
+        let mv = self.history.pop().expect("no moves to undo");
+
+        let board = &mut self.board;
+        board.ply -= 1;
+
+        match mv {
+            Move::Drop(column) => {
+                board.heights[column as usize] -= 1;
+                board.players[board.ply & 1] ^= 1 << board.heights[column as usize];
+            }
+            Move::Pop(column) => {
+                let mask = Self::column_mask(column);
+                for player in &mut board.players {
+                    *player = (*player & !mask) | (((*player & mask) << 1) & mask);
+                }
+                board.players[board.ply & 1] |= Self::bottom_cell(column);
+                board.heights[column as usize] += 1;
+            }
+        }
+    }
+
+    /// Gets the token of the winner, if there is one.
+    ///
+    /// If both players have a line, the player who made the last move wins.
+    pub fn winner(&self) -> Option<Token> {
+        let last = self.current_player().opponent();
+        let board_of = |token: Token| self.board.players[token.player() as usize - 1];
+
+        if GenericBoard::<W, H, N>::is_win(board_of(last)) {
+            Some(last)
+        } else if GenericBoard::<W, H, N>::is_win(board_of(last.opponent())) {
+            Some(last.opponent())
+        } else {
+            None
+        }
+    }
+
+    /// Gets the bitmask of the cells in the given column.
+    fn column_mask(column: Column) -> BitBoard {
+        ((1 << H) - 1) << (column as usize * (H + 1))
+    }
+
+    /// Gets the bitmask of the bottom cell of the given column.
+    fn bottom_cell(column: Column) -> BitBoard {
+        1 << (column as usize * (H + 1))
+    }
+}
+
+impl<const W: usize, const H: usize, const N: usize> Default for GenericPopOutBoard<W, H, N> {
+    fn default() -> GenericPopOutBoard<W, H, N> {
+        GenericPopOutBoard::new()
+    }
+}
+
+impl<const W: usize, const H: usize, const N: usize> fmt::Display for GenericPopOutBoard<W, H, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.board.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_own_tokens_are_popped() {
+        let mut board = PopOutBoard::new();
+        assert!(!board.is_legal(Move::Pop(0)));
+
+        board.make_move(Move::Drop(0));
+        assert!(!board.is_legal(Move::Pop(0)));
+
+        board.make_move(Move::Drop(1));
+        assert!(board.is_legal(Move::Pop(0)));
+        assert!(!board.is_legal(Move::Pop(1)));
+        assert!(!board.is_legal(Move::Pop(WIDTH)));
+        assert_eq!(
+            board
+                .legal_moves()
+                .filter(|mv| matches!(mv, Move::Pop(_)))
+                .collect::<Vec<_>>(),
+            [Move::Pop(0)]
+        );
+    }
+
+    #[test]
+    fn pop_moves_the_column_down_and_is_undone() {
+        let mut board = PopOutBoard::new();
+        for &column in &[0, 0, 1, 0] {
+            board.make_move(Move::Drop(column));
+        }
+
+        board.make_move(Move::Pop(0));
+        assert_eq!(board.token_at(0, 0), Some(Token::Player2));
+        assert_eq!(board.token_at(1, 0), Some(Token::Player2));
+        assert_eq!(board.token_at(2, 0), None);
+
+        board.undo_move();
+        assert_eq!(board.token_at(0, 0), Some(Token::Player1));
+        assert_eq!(board.token_at(2, 0), Some(Token::Player2));
+        assert!(board.is_legal(Move::Pop(0)));
+    }
+
+    #[test]
+    fn pop_completing_two_lines_wins_for_the_popper() {
+        let mut board = PopOutBoard::new();
+        for &column in &[0, 3, 1, 0, 4, 4, 5, 5, 6, 6, 3, 3, 2] {
+            board.make_move(Move::Drop(column));
+        }
+        assert_eq!(board.winner(), None);
+
+        // The first player's token falls into the bottom row and the second player's into the
+        // row above, completing a line for each.
+        board.make_move(Move::Pop(3));
+        assert_eq!(board.winner(), Some(Token::Player2));
+    }
+}
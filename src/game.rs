@@ -1,43 +1,537 @@
-use crate::board::Board;
-use crate::player::{Player, Token};
+use std::fs;
+use std::io;
+use std::path::Path;
 
-pub struct Game<P1: Player, P2: Player> {
+use crate::board::{Board, Column, GameResult};
+use crate::player::{Player, PlayerAction, Token};
+
+pub struct Game<'a, P1: Player, P2: Player> {
     board: Board,
+    /// Moves that were undone, with the most recently undone move last.
+    redo: Vec<Column>,
+    /// The number of moves after which the game is drawn, if there is a limit.
+    move_limit: Option<usize>,
+    /// The player who resigned the game, if one did.
+    resigned: Option<Token>,
+    /// The player who quit on the latest step, if one did.
+    quitter: Option<Token>,
     player1: P1,
     player2: P2,
+    observer: Option<Box<Observer<'a>>>,
 }
 
-impl<P1: Player, P2: Player> Game<P1, P2> {
+/// A callback given the board, the token of the player who moved and the column after a move.
+type Observer<'a> = dyn FnMut(&Board, Token, Column) + 'a;
+
+impl<'a, P1: Player, P2: Player> Game<'a, P1, P2> {
     /// Creates a new game.
-    pub fn new(player1: P1, player2: P2) -> Game<P1, P2> {
+    pub fn new(player1: P1, player2: P2) -> Game<'a, P1, P2> {
+        Game::from_board(Board::new(), player1, player2)
+    }
+
+    /// Creates a game that starts from the given board, with the current player of the board to
+    /// move.
+    pub fn from_board(board: Board, mut player1: P1, mut player2: P2) -> Game<'a, P1, P2> {
+        player1.new_game();
+        player2.new_game();
+
         Game {
-            board: Board::new(),
+            board,
+            redo: Vec::new(),
+            move_limit: None,
+            resigned: None,
+            quitter: None,
             player1,
             player2,
+            observer: None,
+        }
+    }
+
+    /// Limits the game to the given number of moves, after which it is drawn if no player has
+    /// won, even if the board is not full.
+    pub fn with_move_limit(mut self, limit: usize) -> Game<'a, P1, P2> {
+        self.move_limit = Some(limit);
+        self
+    }
+
+    /// Sets a callback that is called after each move a player makes, with the board after the
+    /// move, the token of the player who made it and the column it was played in.
+    ///
+    /// The callback may borrow from its surroundings for as long as the game lives, such as to
+    /// collect the moves in a local `Vec`.
+    pub fn with_observer(
+        mut self,
+        observer: impl FnMut(&Board, Token, Column) + 'a,
+    ) -> Game<'a, P1, P2> {
+        self.observer = Some(Box::new(observer));
+        self
+    }
+
+    /// Gets the game board.
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Gets the result of the game so far, where a player who resigned has lost, and the game is
+    /// a draw once the move limit is reached.
+    pub fn result(&self) -> GameResult {
+        if let Some(token) = self.resigned {
+            return GameResult::Win(token.opponent());
+        }
+
+        let result = self.board.result();
+        match self.move_limit {
+            Some(limit) if !result.is_over() && self.board.ply() >= limit => GameResult::Draw,
+            _ => result,
         }
     }
 
-    /// Plays the game and returns the board and the winner if there is one.
-    pub fn play(self) -> (Board, Option<Token>) {
-        let Game {
-            mut board,
-            mut player1,
-            mut player2,
-        } = self;
+    /// Gets the player who quit on the latest step, if one did.
+    ///
+    /// A game that was quit is left unfinished, and can be continued with another step.
+    pub fn quitter(&self) -> Option<Token> {
+        self.quitter
+    }
+
+    /// Gets whether the game is finished, either by a win, a resignation or a draw.
+    pub fn is_over(&self) -> bool {
+        self.result().is_over()
+    }
+
+    /// Asks the current player for an action and takes it, returning the result of the game
+    /// after the action.
+    ///
+    /// Playing a move clears the moves that can be redone. An undo takes back the last move of
+    /// the current player and the reply of the opponent, if the player has made a move, so the
+    /// player is asked again on the next step. Quitting takes no action, and is recorded until
+    /// the next step in [`Game::quitter`]. If the action finishes the game, both players are told
+    /// the result with [`Player::game_over`]. If the game is already over, no action is
+    /// taken and the result is returned as it is.
+    pub fn step(&mut self) -> GameResult {
+        let result = self.result();
+        if result.is_over() {
+            return result;
+        }
+
+        self.quitter = None;
 
-        loop {
-            let token = board.current_player();
-            let column = match token {
-                Token::Player1 => player1.decide_move(&board, token),
-                Token::Player2 => player2.decide_move(&board, token),
-            };
-            board.make_move(column);
+        let token = self.board.current_player();
+        let action = match token {
+            Token::Player1 => self.player1.decide_action(&self.board, token),
+            Token::Player2 => self.player2.decide_action(&self.board, token),
+        };
 
-            match board.winner() {
-                Some(winner) => return (board, Some(winner)),
-                None if board.legal_moves().next().is_none() => return (board, None),
-                None => {}
+        match action {
+            PlayerAction::Move(column) => {
+                self.board.make_move(column);
+                self.redo.clear();
+
+                if let Some(observer) = &mut self.observer {
+                    observer(&self.board, token, column);
+                }
+            }
+            PlayerAction::Undo => {
+                if self.board.ply() >= 2 {
+                    self.undo();
+                    self.undo();
+                }
             }
+            PlayerAction::Resign => self.resigned = Some(token),
+            PlayerAction::Quit => self.quitter = Some(token),
+        }
+
+        let result = self.result();
+        if result.is_over() {
+            self.player1.game_over(&self.board, result);
+            self.player2.game_over(&self.board, result);
+        }
+
+        result
+    }
+
+    /// Saves the names of the players and the moves played so far to a file, in the format of
+    /// [`Board::save_to_path`].
+    ///
+    /// The board can be loaded again with [`Board::load_from_path`].
+    pub fn save_to_path(&self, path: &Path) -> io::Result<()> {
+        let contents = format!(
+            "player1: {}\nplayer2: {}\nmoves: {}\n",
+            self.player1.name(),
+            self.player2.name(),
+            self.board.to_move_string()
+        );
+        fs::write(path, contents)
+    }
+
+    /// Takes back the previous move, returning its column if a move has been played.
+    ///
+    /// A resignation is also taken back, without taking back a move.
+    pub fn undo(&mut self) -> Option<Column> {
+        if self.resigned.take().is_some() {
+            return None;
         }
+
+        let column = *self.board.move_history().last()?;
+        self.board.undo_move();
+        self.redo.push(column);
+
+        Some(column)
+    }
+
+    /// Plays the most recently undone move again, returning its column if there is one.
+    pub fn redo(&mut self) -> Option<Column> {
+        let column = self.redo.pop()?;
+        self.board.make_move(column);
+
+        Some(column)
+    }
+
+    /// Plays the game to the end and returns the board and the result.
+    ///
+    /// If a player quits, the game stops early with the result still in progress.
+    pub fn play(self) -> (Board, GameResult) {
+        self.play_with(|_, _, _| {})
+    }
+
+    /// Plays the game to the end like [`Game::play`], calling `on_move` after each move with the
+    /// board after the move, the token of the player who made it and the column it was played in.
+    pub fn play_with(
+        mut self,
+        mut on_move: impl FnMut(&Board, Token, Column),
+    ) -> (Board, GameResult) {
+        self.quitter = None;
+
+        let mut result = self.result();
+        while !result.is_over() && self.quitter.is_none() {
+            let token = self.board.current_player();
+            let ply = self.board.ply();
+            result = self.step();
+
+            // Undoing, resigning and quitting play no move.
+            if self.board.ply() == ply + 1 {
+                let column = *self
+                    .board
+                    .move_history()
+                    .last()
+                    .expect("no move was played");
+                on_move(&self.board, token, column);
+            }
+        }
+
+        (self.board, result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::env;
+    use std::process;
+    use std::vec;
+
+    use super::*;
+    use crate::player::scripted::ScriptedPlayer;
+
+    /// A player that takes a fixed list of actions in order.
+    struct Actions(vec::IntoIter<PlayerAction>);
+
+    impl Actions {
+        fn new(actions: Vec<PlayerAction>) -> Actions {
+            Actions(actions.into_iter())
+        }
+    }
+
+    impl Player for Actions {
+        fn decide_action(&mut self, _board: &Board, _token: Token) -> PlayerAction {
+            self.0.next().unwrap_or(PlayerAction::Quit)
+        }
+    }
+
+    #[test]
+    fn observer_sees_every_move_in_order() {
+        let player1 = ScriptedPlayer::new(vec![3, 3, 3, 3]);
+        let player2 = ScriptedPlayer::new(vec![2, 4, 2]);
+
+        let mut moves = Vec::new();
+        let game = Game::new(player1, player2)
+            .with_observer(|board, token, column| moves.push((board.ply(), token, column)));
+        let (_, result) = game.play();
+
+        assert_eq!(result, GameResult::Win(Token::Player1));
+        assert_eq!(
+            moves,
+            [
+                (1, Token::Player1, 3),
+                (2, Token::Player2, 2),
+                (3, Token::Player1, 3),
+                (4, Token::Player2, 4),
+                (5, Token::Player1, 3),
+                (6, Token::Player2, 2),
+                (7, Token::Player1, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn undo_takes_back_a_move_of_each_player() {
+        use PlayerAction::{Move, Undo};
+
+        let player1 = Actions::new(vec![Move(3), Move(4)]);
+        let player2 = Actions::new(vec![Move(3), Undo, Move(2)]);
+        let mut game = Game::new(player1, player2);
+
+        for _ in 0..3 {
+            game.step();
+        }
+        assert_eq!(game.board().move_history(), [3, 3, 4]);
+
+        assert_eq!(game.step(), GameResult::InProgress);
+        assert_eq!(game.board().move_history(), [3]);
+        assert_eq!(game.board().current_player(), Token::Player2);
+
+        game.step();
+        assert_eq!(game.board().move_history(), [3, 2]);
+    }
+
+    #[test]
+    fn undo_without_a_move_of_the_player_does_nothing() {
+        use PlayerAction::{Move, Undo};
+
+        let player1 = Actions::new(vec![Undo, Move(3)]);
+        let player2 = Actions::new(vec![Undo, Move(2)]);
+        let mut game = Game::new(player1, player2);
+
+        assert_eq!(game.step(), GameResult::InProgress);
+        assert_eq!(game.board().ply(), 0);
+        game.step();
+
+        assert_eq!(game.step(), GameResult::InProgress);
+        assert_eq!(game.board().move_history(), [3]);
+        game.step();
+        assert_eq!(game.board().move_history(), [3, 2]);
+    }
+
+    #[test]
+    fn undone_moves_are_redone() {
+        let player1 = ScriptedPlayer::new(vec![3, 4, 0]);
+        let player2 = ScriptedPlayer::new(vec![3, 2]);
+        let mut game = Game::new(player1, player2);
+        for _ in 0..4 {
+            game.step();
+        }
+        let board = *game.board();
+
+        assert_eq!(game.undo(), Some(2));
+        assert_eq!(game.undo(), Some(4));
+        assert_eq!(game.board().move_history(), [3, 3]);
+        assert_eq!(game.redo(), Some(4));
+        assert_eq!(game.redo(), Some(2));
+        assert_eq!(game.redo(), None);
+        assert_eq!(game.board().move_history(), board.move_history());
+
+        // Playing a move forgets the moves that were undone.
+        game.undo();
+        game.undo();
+        game.step();
+        assert_eq!(game.board().move_history(), [3, 3, 0]);
+        assert_eq!(game.redo(), None);
+    }
+
+    #[test]
+    fn undo_takes_back_moves_and_resignations() {
+        use PlayerAction::{Move, Resign};
+
+        let player1 = Actions::new(vec![Move(3)]);
+        let player2 = Actions::new(vec![Resign]);
+        let mut game = Game::new(player1, player2);
+        assert_eq!(game.undo(), None);
+
+        game.step();
+        assert_eq!(game.step(), GameResult::Win(Token::Player1));
+
+        assert_eq!(game.undo(), None);
+        assert_eq!(game.result(), GameResult::InProgress);
+        assert_eq!(game.board().move_history(), [3]);
+
+        assert_eq!(game.undo(), Some(3));
+        assert_eq!(game.undo(), None);
+        assert_eq!(game.board().ply(), 0);
+    }
+
+    #[test]
+    fn move_limit_draws_the_game() {
+        let player1 = ScriptedPlayer::new(vec![0, 1]);
+        let player2 = ScriptedPlayer::new(vec![6, 5]);
+        let (board, result) = Game::new(player1, player2).with_move_limit(4).play();
+
+        assert_eq!(result, GameResult::Draw);
+        assert_eq!(board.move_history(), [0, 6, 1, 5]);
+    }
+
+    #[test]
+    fn win_on_the_last_move_beats_the_limit() {
+        let player1 = ScriptedPlayer::new(vec![0, 0, 0, 0]);
+        let player2 = ScriptedPlayer::new(vec![6, 6, 5]);
+        let (_, result) = Game::new(player1, player2).with_move_limit(7).play();
+
+        assert_eq!(result, GameResult::Win(Token::Player1));
+    }
+
+    #[test]
+    fn each_step_plays_a_move() {
+        let player1 = ScriptedPlayer::new(vec![3, 3, 3, 3]);
+        let player2 = ScriptedPlayer::new(vec![2, 2, 2]);
+        let mut game = Game::new(player1, player2);
+
+        for ply in 1..7 {
+            assert_eq!(game.step(), GameResult::InProgress);
+            assert_eq!(game.board().ply(), ply);
+            assert!(!game.is_over());
+        }
+        assert_eq!(game.step(), GameResult::Win(Token::Player1));
+        assert!(game.is_over());
+
+        // The players have no moves left, so they must not be asked for another.
+        assert_eq!(game.step(), GameResult::Win(Token::Player1));
+        assert_eq!(game.board().ply(), 7);
+    }
+
+    #[test]
+    fn game_continues_from_the_board() {
+        let board = Board::from_move_string("121212").unwrap();
+        let game = Game::from_board(
+            board,
+            ScriptedPlayer::new(vec![0]),
+            ScriptedPlayer::new(vec![]),
+        );
+        let (board, result) = game.play();
+        assert_eq!(result, GameResult::Win(Token::Player1));
+        assert_eq!(board.move_history(), [0, 1, 0, 1, 0, 1, 0]);
+
+        // The second player is to move on this board.
+        let board = Board::from_move_string("12121").unwrap();
+        let mut game = Game::from_board(
+            board,
+            ScriptedPlayer::new(vec![]),
+            ScriptedPlayer::new(vec![0]),
+        );
+        game.step();
+        assert_eq!(game.board().move_history(), [0, 1, 0, 1, 0, 0]);
+    }
+
+    #[test]
+    fn game_from_a_finished_board_is_over() {
+        let board = Board::from_move_string("1212121").unwrap();
+        let game = Game::from_board(
+            board,
+            ScriptedPlayer::new(vec![]),
+            ScriptedPlayer::new(vec![]),
+        );
+
+        assert!(game.is_over());
+        assert_eq!(game.play(), (board, GameResult::Win(Token::Player1)));
+    }
+
+    #[test]
+    fn play_with_is_told_of_moves_only() {
+        use PlayerAction::{Move, Resign, Undo};
+
+        let player1 = Actions::new(vec![Move(3), Move(4), Resign]);
+        let player2 = Actions::new(vec![Move(3), Undo, Move(2)]);
+
+        let mut moves = Vec::new();
+        let (board, result) = Game::new(player1, player2)
+            .play_with(|board, token, column| moves.push((board.ply(), token, column)));
+
+        assert_eq!(result, GameResult::Win(Token::Player2));
+        assert_eq!(board.move_history(), [3, 2]);
+        assert_eq!(
+            moves,
+            [
+                (1, Token::Player1, 3),
+                (2, Token::Player2, 3),
+                (3, Token::Player1, 4),
+                (2, Token::Player2, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn saved_game_is_loaded_as_its_board() {
+        let player1 = ScriptedPlayer::new(vec![3, 4]);
+        let player2 = ScriptedPlayer::new(vec![3]);
+        let mut game = Game::new(player1, player2);
+        for _ in 0..3 {
+            game.step();
+        }
+
+        let path = env::temp_dir().join(format!("connect4-game-{}.txt", process::id()));
+        game.save_to_path(&path).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        let board = Board::load_from_path(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            contents,
+            "player1: Scripted\nplayer2: Scripted\nmoves: 445\n"
+        );
+        assert_eq!(board.unwrap().move_history(), [3, 3, 4]);
+    }
+
+    /// A player that makes scripted moves and records the results it is told of.
+    struct Told<'r> {
+        moves: ScriptedPlayer,
+        told: &'r RefCell<Vec<(&'static str, Board, GameResult)>>,
+        name: &'static str,
+    }
+
+    impl Player for Told<'_> {
+        fn decide_action(&mut self, board: &Board, token: Token) -> PlayerAction {
+            self.moves.decide_action(board, token)
+        }
+
+        fn game_over(&mut self, board: &Board, result: GameResult) {
+            self.told.borrow_mut().push((self.name, *board, result));
+        }
+    }
+
+    #[test]
+    fn both_players_are_told_the_result_once() {
+        let told = RefCell::new(Vec::new());
+        let player1 = Told {
+            moves: ScriptedPlayer::new(vec![3, 3, 3, 3]),
+            told: &told,
+            name: "player1",
+        };
+        let player2 = Told {
+            moves: ScriptedPlayer::new(vec![2, 2, 2]),
+            told: &told,
+            name: "player2",
+        };
+
+        let mut game = Game::new(player1, player2);
+        while !game.is_over() {
+            game.step();
+        }
+        game.step();
+
+        let board = *game.board();
+        let result = GameResult::Win(Token::Player1);
+        assert_eq!(
+            told.into_inner(),
+            [("player1", board, result), ("player2", board, result)]
+        );
+    }
+
+    #[test]
+    fn boxed_players_play_a_game() {
+        let player1: Box<dyn Player> = Box::new(ScriptedPlayer::new(vec![3]));
+        let player2: Box<dyn Player> = Box::new(Actions::new(vec![PlayerAction::Resign]));
+        assert_eq!(player1.name(), "Scripted");
+
+        let game = Game::new(player1, player2);
+        let (board, result) = game.play();
+        assert_eq!(result, GameResult::Win(Token::Player1));
+        assert_eq!(board.move_history(), [3]);
     }
 }
@@ -1,25 +1,45 @@
+use std::env;
+use std::io::{self, BufReader};
+
+use crate::board::GameResult;
 use crate::game::Game;
 use crate::player::ai::{AIPlayer, Difficulty};
 use crate::player::console::ConsolePlayer;
+use crate::player::Player;
 
 pub mod board;
 pub mod game;
 pub mod player;
+pub mod protocol;
+pub mod replay;
+pub mod selfplay;
+pub mod solver;
 
 fn main() {
+    // Run as an engine for other programs with `connect4 protocol`.
+    if env::args().nth(1).as_deref() == Some("protocol") {
+        if let Err(err) = protocol::run(BufReader::new(io::stdin()), io::stdout().lock()) {
+            eprintln!("Error: {}", err);
+        }
+        return;
+    }
+
     let player1 = ConsolePlayer::new();
     let player2 = AIPlayer::new(Difficulty::Hard);
     // let player1 = AIPlayer::new(Difficulty::Master);
     // let player2 = ConsolePlayer::new();
 
+    let names = [player1.name().into_owned(), player2.name().into_owned()];
+
     let game = Game::new(player1, player2);
-    let (board, winner) = game.play();
+    let (board, result) = game.play();
 
     println!("\nFinal board:\n{}", board);
 
     println!();
-    match winner {
-        Some(token) => println!("Player {} wins", token.player()),
-        None => println!("The game ended in a draw"),
+    match result {
+        GameResult::Win(token) => println!("{} wins", names[token.player() as usize - 1]),
+        GameResult::Draw => println!("The game ended in a draw"),
+        GameResult::InProgress => println!("The game was quit"),
     }
 }
@@ -1,12 +1,23 @@
+use std::borrow::Cow;
+use std::error::Error;
 use std::fmt;
+use std::str::FromStr;
 
-use crate::board::{Board, Column};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::board::{Board, Column, GameResult};
 
 pub mod ai;
 pub mod console;
+pub mod mcts;
+pub mod network;
+pub mod random;
+pub mod scripted;
 
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Token {
     Player1 = 1,
     Player2 = 2,
@@ -38,9 +49,186 @@ impl fmt::Display for Token {
     }
 }
 
+impl FromStr for Token {
+    type Err = ParseTokenError;
+
+    /// Parses the symbol of a token, as written by [`Token::char`].
+    fn from_str(s: &str) -> Result<Token, ParseTokenError> {
+        [Token::Player1, Token::Player2]
+            .iter()
+            .copied()
+            .find(|token| token.char() == s)
+            .ok_or_else(|| ParseTokenError {
+                symbol: s.to_owned(),
+            })
+    }
+}
+
+/// An error from parsing a symbol that is not the symbol of a token.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseTokenError {
+    /// The symbol that was parsed.
+    pub symbol: String,
+}
+
+impl fmt::Display for ParseTokenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unknown token {:?}, expected {:?} or {:?}",
+            self.symbol,
+            Token::Player1.char(),
+            Token::Player2.char()
+        )
+    }
+}
+
+impl Error for ParseTokenError {}
+
+/// An action a player takes on their turn.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PlayerAction {
+    /// Plays a move in the column.
+    Move(Column),
+    /// Takes back the last move of the player, along with the moves of the opponent since.
+    Undo,
+    /// Gives up the game, which the opponent wins.
+    Resign,
+    /// Stops playing, leaving the game unfinished.
+    Quit,
+}
+
 pub trait Player {
-    /// Gets the move the player wishes to make.
+    /// Gets the action the player wishes to take on their turn in a game, such as the move they
+    /// wish to make.
     ///
-    /// `board` is a copy of the game board and `token` is the token the player uses.
-    fn decide_move(&mut self, board: &Board, token: Token) -> Column;
+    /// `board` is a copy of the game board and `token` is the token the player uses. The game on
+    /// the board is not over, so there is always a legal move. Players that cannot give a move,
+    /// such as when their input has ended, quit rather than exiting the program.
+    fn decide_action(&mut self, board: &Board, token: Token) -> PlayerAction;
+
+    /// Gets the name of the player, for display.
+    fn name(&self) -> Cow<'_, str> {
+        Cow::Borrowed("Player")
+    }
+
+    /// Called when a new game is started with the player, before it is asked for any moves.
+    fn new_game(&mut self) {}
+
+    /// Called when a game with the player is finished, with the final board and the result.
+    fn game_over(&mut self, _board: &Board, _result: GameResult) {}
+}
+
+/// Creates a player that decides its moves by calling `f` with the board and its token.
+pub fn from_fn<F>(f: F) -> FromFn<F>
+where
+    F: FnMut(&Board, Token) -> Column,
+{
+    FromFn(f)
+}
+
+/// A player that decides its moves with a closure, created by [`from_fn`].
+pub struct FromFn<F>(F);
+
+impl<F> Player for FromFn<F>
+where
+    F: FnMut(&Board, Token) -> Column,
+{
+    fn decide_action(&mut self, board: &Board, token: Token) -> PlayerAction {
+        PlayerAction::Move((self.0)(board, token))
+    }
+}
+
+/// Boxed players are players, so a game can be played between players chosen at runtime, as
+/// `Game<Box<dyn Player>, Box<dyn Player>>`.
+impl<P: Player + ?Sized> Player for Box<P> {
+    fn decide_action(&mut self, board: &Board, token: Token) -> PlayerAction {
+        (**self).decide_action(board, token)
+    }
+
+    fn name(&self) -> Cow<'_, str> {
+        (**self).name()
+    }
+
+    fn new_game(&mut self) {
+        (**self).new_game();
+    }
+
+    fn game_over(&mut self, board: &Board, result: GameResult) {
+        (**self).game_over(board, result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Game;
+    use crate::player::random::RandomPlayer;
+
+    #[test]
+    fn players_have_a_default_name() {
+        let player = from_fn(|board, _| board.ordered_moves().next().unwrap());
+        assert_eq!(player.name(), "Player");
+    }
+
+    #[test]
+    fn from_fn_is_asked_for_moves() {
+        let board = Board::from_move_string("44").unwrap();
+        let mut asked = Vec::new();
+
+        let mut player = from_fn(|board: &Board, token| {
+            asked.push((board.ply(), token));
+            board.ordered_moves().next().unwrap()
+        });
+        let action = player.decide_action(&board, Token::Player1);
+
+        assert_eq!(action, PlayerAction::Move(3));
+        assert_eq!(asked, [(2, Token::Player1)]);
+    }
+
+    #[test]
+    fn from_fn_plays_a_full_game() {
+        for seed in 0..10 {
+            let lowest = from_fn(|board, _| board.legal_moves().next().unwrap());
+            let game = Game::new(lowest, RandomPlayer::with_seed(seed));
+
+            let (board, result) = game.play_with(|board, token, column| {
+                // Every column to the left of each move of the first player is full.
+                if token == Token::Player1 {
+                    assert!((0..column).all(|column| !board.is_legal(column)));
+                }
+            });
+            assert!(result.is_over());
+            assert_eq!(result, board.result());
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn token_is_serialized_as_a_variant() {
+        use serde_test::{assert_tokens, Token as SerdeToken};
+
+        assert_tokens(
+            &Token::Player2,
+            &[SerdeToken::UnitVariant {
+                name: "Token",
+                variant: "Player2",
+            }],
+        );
+    }
+
+    #[test]
+    fn token_symbols_are_parsed() {
+        for &token in &[Token::Player1, Token::Player2] {
+            assert_eq!(token.char().parse(), Ok(token));
+        }
+
+        let err = "X".parse::<Token>().unwrap_err();
+        assert_eq!(err.symbol, "X");
+        assert_eq!(
+            err.to_string(),
+            "unknown token \"X\", expected \"x\" or \"o\""
+        );
+        assert!("".parse::<Token>().is_err());
+    }
 }
@@ -1,12 +1,32 @@
-use std::collections::HashMap;
-use std::ops::Range;
+use std::borrow::Cow;
+use std::cmp::{self, Reverse};
+use std::error::Error;
+use std::fmt;
+use std::mem;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(not(feature = "rayon"))]
+use std::sync::mpsc;
+use std::sync::Arc;
+#[cfg(not(feature = "rayon"))]
+use std::thread;
+use std::time::{Duration, Instant};
 
-use rand::prelude::ThreadRng;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 
-use crate::board::{BitBoard, Board, Column, ColumnDiff, PeekableBoard, HEIGHT, WIDTH};
-use crate::player::{Player, Token};
+use crate::board::{Board, Column, PeekableBoard, BOARD_SIZE, WIDTH};
+use crate::player::ai::book::OpeningBook;
+use crate::player::ai::eval::{DefaultEvaluator, Evaluator, HeuristicWeights};
+use crate::player::{Player, PlayerAction, Token};
+use crate::solver::{self, Outcome, Solver};
 
+pub mod book;
+pub mod eval;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Difficulty {
     Easy = 3,
     Medium = 5,
@@ -15,73 +35,842 @@ pub enum Difficulty {
     Unfair = 11,
 }
 
-pub struct AIPlayer {
+impl Difficulty {
+    /// All of the difficulties, from easiest to hardest.
+    pub const ALL: &'static [Difficulty] = &[
+        Difficulty::Easy,
+        Difficulty::Medium,
+        Difficulty::Hard,
+        Difficulty::Master,
+        Difficulty::Unfair,
+    ];
+
+    /// Gets the name of the difficulty.
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Medium => "Medium",
+            Difficulty::Hard => "Hard",
+            Difficulty::Master => "Master",
+            Difficulty::Unfair => "Unfair",
+        }
+    }
+}
+
+impl From<Difficulty> for usize {
+    /// Gets the search depth of the difficulty.
+    fn from(difficulty: Difficulty) -> usize {
+        difficulty as usize
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = ParseDifficultyError;
+
+    /// Parses the name of a difficulty, ignoring case.
+    fn from_str(s: &str) -> Result<Difficulty, ParseDifficultyError> {
+        Difficulty::ALL
+            .iter()
+            .copied()
+            .find(|difficulty| difficulty.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| ParseDifficultyError { name: s.to_owned() })
+    }
+}
+
+/// An error from parsing the name of a difficulty that does not exist.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseDifficultyError {
+    /// The name that was parsed.
+    pub name: String,
+}
+
+impl fmt::Display for ParseDifficultyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown difficulty {:?}, expected one of ", self.name)?;
+        for (i, difficulty) in Difficulty::ALL.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", difficulty)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ParseDifficultyError {}
+
+/// An error from asking for a move on a board where the game is already over.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct NoLegalMove;
+
+impl fmt::Display for NoLegalMove {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("no legal moves, the game is over")
+    }
+}
+
+impl Error for NoLegalMove {}
+
+pub struct AIPlayer<E: Evaluator = DefaultEvaluator> {
+    name: String,
     depth: usize,
-    ttable: TTable,
-    rng: ThreadRng,
+    threads: usize,
+    time_budget: Option<Duration>,
+    ttable: TranspositionTable,
+    solver: Solver,
+    rng: StdRng,
+    stats: SearchStats,
+    evaluator: Arc<E>,
+    book: Option<OpeningBook>,
+    /// The probability of playing a random move rather than the best move.
+    blunder_rate: f64,
 }
 
+/// The memory used by the transposition table of a new [`AIPlayer`], in bytes.
+pub const DEFAULT_TABLE_MEMORY: usize = 16 * 1024 * 1024;
+
 impl AIPlayer {
     pub fn new(difficulty: Difficulty) -> AIPlayer {
-        let depth = difficulty as usize;
+        AIPlayer::with_rng(
+            format!("AI ({})", difficulty),
+            difficulty.into(),
+            StdRng::from_entropy(),
+        )
+    }
 
+    /// Creates a player that searches to the given depth, rather than that of a difficulty.
+    pub fn with_depth(depth: usize) -> AIPlayer {
+        AIPlayer::with_rng(
+            format!("AI (depth {})", depth),
+            depth,
+            StdRng::from_entropy(),
+        )
+    }
+
+    /// Creates a player that plays the moves recommended by the opening book when there are
+    /// any, and otherwise searches to the depth of the difficulty.
+    pub fn with_book(difficulty: Difficulty, book: OpeningBook) -> AIPlayer {
         AIPlayer {
+            book: Some(book),
+            ..AIPlayer::new(difficulty)
+        }
+    }
+
+    /// Creates a player that breaks ties between equally good moves with a random number
+    /// generator seeded by `seed`, so it always makes the same move on the same board.
+    pub fn with_seed(difficulty: Difficulty, seed: u64) -> AIPlayer {
+        AIPlayer::with_rng(
+            format!("AI ({})", difficulty),
+            difficulty.into(),
+            StdRng::seed_from_u64(seed),
+        )
+    }
+
+    fn with_rng(name: String, depth: usize, rng: StdRng) -> AIPlayer {
+        assert!(depth > 0, "depth must be at least 1");
+
+        AIPlayer {
+            name,
             depth,
-            ttable: TTable::with_capacity(depth * (WIDTH as usize)),
-            rng: rand::thread_rng(),
+            threads: default_threads(),
+            time_budget: None,
+            ttable: TranspositionTable::with_memory(DEFAULT_TABLE_MEMORY),
+            solver: Solver::new(),
+            rng,
+            stats: SearchStats::default(),
+            evaluator: Arc::new(DefaultEvaluator::default()),
+            book: None,
+            blunder_rate: 0.0,
         }
     }
+
+    /// Sets the weights used to score boards once the search reaches its depth.
+    pub fn with_weights(self, weights: HeuristicWeights) -> AIPlayer {
+        self.with_evaluator(DefaultEvaluator::new(weights))
+    }
 }
 
-impl Player for AIPlayer {
-    fn decide_move(&mut self, board: &Board, token: Token) -> Column {
-        let mut board = *board;
+impl<E: Evaluator> AIPlayer<E> {
+    /// Sets the evaluator used to score boards once the search reaches its depth.
+    pub fn with_evaluator<F: Evaluator>(self, evaluator: F) -> AIPlayer<F> {
+        AIPlayer {
+            name: self.name,
+            depth: self.depth,
+            threads: self.threads,
+            time_budget: self.time_budget,
+            ttable: self.ttable,
+            solver: self.solver,
+            rng: self.rng,
+            stats: self.stats,
+            evaluator: Arc::new(evaluator),
+            book: self.book,
+            blunder_rate: self.blunder_rate,
+        }
+    }
 
-        let mut best_moves = [0; WIDTH as usize];
-        let mut len_best_moves = 0;
-        let mut value_best_move = Score::MIN;
+    /// Sets the name of the player, which defaults to the difficulty, eg. `AI (Hard)`.
+    pub fn with_name(mut self, name: impl Into<String>) -> AIPlayer<E> {
+        self.name = name.into();
+        self
+    }
 
-        for column in board.legal_moves() {
-            let value = negamax(
-                &mut self.ttable,
-                board.peekable(column),
-                self.depth,
-                Score::MIN,
-                Score::MAX,
-                token.opponent(),
-            )
-            .saturating_neg();
-
-            match value_best_move.saturating_sub(value) {
-                0 => {
-                    best_moves[len_best_moves] = column;
-                    len_best_moves += 1;
-                }
-                diff if diff < 0 => {
-                    value_best_move = value;
-                    best_moves[0] = column;
-                    len_best_moves = 1;
-                }
-                _ => {}
-            }
+    /// Sets the number of threads used to search the root moves in parallel.
+    ///
+    /// Each thread searches with its own transposition table, so the table kept between moves is
+    /// only used when searching on a single thread. With the `rayon` feature, the searches run on
+    /// the global thread pool of rayon and default to one for each of its threads, otherwise a
+    /// thread is spawned for each and the search defaults to a single thread.
+    pub fn with_threads(mut self, threads: usize) -> AIPlayer<E> {
+        assert!(threads > 0, "at least one thread is required");
+
+        self.threads = threads;
+        self
+    }
+
+    /// Sets the memory used by the transposition table, in bytes.
+    ///
+    /// The table holds a power of two entries, so it may use up to half as much memory as given.
+    /// When searching in parallel, each thread uses a table of this size.
+    pub fn with_table_memory(mut self, bytes: usize) -> AIPlayer<E> {
+        self.ttable = TranspositionTable::with_memory(bytes);
+        self
+    }
+
+    /// Limits the time spent searching each move to the given budget.
+    ///
+    /// Instead of searching to the depth of the difficulty, the search deepens until the budget
+    /// runs out and then uses the deepest search that completed. The shallowest search always
+    /// completes, so the budget may be exceeded slightly.
+    pub fn with_time_budget(mut self, budget: Duration) -> AIPlayer<E> {
+        self.time_budget = Some(budget);
+        self
+    }
+
+    /// Sets the probability of playing a random move instead of the best move, for a weaker and
+    /// less predictable player.
+    ///
+    /// The random move is never one the search found to lose, unless every move loses, and the
+    /// player never blunders away a win it has found.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is not between 0 and 1.
+    pub fn with_blunder_rate(mut self, rate: f64) -> AIPlayer<E> {
+        assert!(
+            (0.0..=1.0).contains(&rate),
+            "blunder rate must be between 0 and 1"
+        );
+
+        self.blunder_rate = rate;
+        self
+    }
+
+    /// Gets the move recommended by the opening book on the board, if there is one.
+    ///
+    /// Playing a move from the book needs no search, so the statistics of the last search are
+    /// reset.
+    fn book_move(&mut self, board: &Board) -> Option<Column> {
+        let column = self.book.as_ref()?.get(board)?;
+        self.stats = SearchStats::default();
+
+        Some(column)
+    }
+
+    /// Gets the depth to deepen the search to.
+    fn max_depth(&self, board: &Board) -> usize {
+        match self.time_budget {
+            // Searching beyond the end of the game changes nothing.
+            Some(_) => BOARD_SIZE as usize - board.ply(),
+            None => self.depth,
+        }
+    }
+
+    /// Decides the move for `token` to play.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the board is full. See [`AIPlayer::try_decide_move`] for boards where the game
+    /// may be over.
+    pub fn decide_move(&mut self, board: &Board, token: Token) -> Column {
+        self.decide_move_with(board, token, SearchOptions::default())
+    }
+
+    /// Decides the move for `token` to play, with the given options for the search.
+    ///
+    /// If the search is cancelled, the best move of the deepest iteration completed so far is
+    /// played.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the board is full. See [`AIPlayer::try_decide_move`] for boards where the game
+    /// may be over.
+    pub fn decide_move_with(
+        &mut self,
+        board: &Board,
+        token: Token,
+        options: SearchOptions<'_>,
+    ) -> Column {
+        if let Some(column) = self.book_move(board) {
+            return column;
+        }
+
+        let analysis = self.analyze_with(board, token, options);
+        self.blunder(board, &analysis).unwrap_or(analysis.best)
+    }
+
+    /// Decides the move for `token` to play, or returns an error if the game is already over,
+    /// either by a win or a draw.
+    pub fn try_decide_move(&mut self, board: &Board, token: Token) -> Result<Column, NoLegalMove> {
+        if board.result().is_over() {
+            return Err(NoLegalMove);
+        }
+
+        Ok(self.decide_move_with(board, token, SearchOptions::default()))
+    }
+
+    /// Picks a random move other than the best move of the analysis, with the probability of
+    /// the blunder rate of the player.
+    ///
+    /// Only moves that are not known to lose are picked, and there is no blunder if the best
+    /// move is known to win.
+    fn blunder(&mut self, board: &Board, analysis: &Analysis) -> Option<Column> {
+        if self.blunder_rate == 0.0 || !self.rng.gen_bool(self.blunder_rate) {
+            return None;
+        }
+
+        // Search values this close to that of a win are wins or losses found by the search.
+        let decided = self.evaluator.win_value() - MAX_DEPTH as Score;
+        if analysis.score >= decided {
+            return None;
+        }
+
+        let safe: Vec<Column> = analysis
+            .moves
+            .iter()
+            .filter(|&&(column, value)| {
+                column != analysis.best && value > -decided && !hands_win(board, column)
+            })
+            .map(|&(column, _)| column)
+            .collect();
+
+        match safe.len() {
+            0 => None,
+            len => Some(safe[self.rng.gen_range(0, len)]),
+        }
+    }
+
+    /// Decides the move for `token` to play, returning it with the statistics of the search.
+    pub fn decide_move_stats(&mut self, board: &Board, token: Token) -> (Column, SearchStats) {
+        let column = self.decide_move(board, token);
+        (column, self.stats)
+    }
+
+    /// Analyzes the board, returning the best move for `token` along with its score, the
+    /// principal variation and the scores of the other moves.
+    pub fn analyze(&mut self, board: &Board, token: Token) -> Analysis {
+        self.analyze_with(board, token, SearchOptions::default())
+    }
+
+    /// Analyzes the board like [`AIPlayer::analyze`], with the given options for the search.
+    pub fn analyze_with(
+        &mut self,
+        board: &Board,
+        token: Token,
+        options: SearchOptions<'_>,
+    ) -> Analysis {
+        let columns = root_columns(board, token);
+        let mut root_moves = self.search_moves(board, &columns, token, options);
+        let moves = sorted_scores(&root_moves);
+
+        // Never hand the opponent a win on their next move if there is any alternative, even if
+        // the search saw no difference.
+        let loses = |column: Column| hands_win(board, column);
+        if root_moves.iter().any(|root_move| !loses(root_move.column)) {
+            root_moves.retain(|root_move| !loses(root_move.column));
         }
 
-        match len_best_moves {
+        let mut best_moves = best_moves(root_moves);
+
+        let best_move = match best_moves.len() {
             0 => panic!("no legal moves"),
-            1 => best_moves[0],
+            1 => best_moves.swap_remove(0),
             // Pick a move from the best moves at random.
-            len => best_moves[self.rng.gen_range(0, len)],
+            len => best_moves.swap_remove(self.rng.gen_range(0, len)),
+        };
+
+        Analysis {
+            best: best_move.column,
+            score: best_move.value,
+            pv: best_move.pv,
+            moves,
+        }
+    }
+
+    /// Searches every legal move for `token` on the board, returning the score of each from the
+    /// perspective of `token`, sorted from best to worst.
+    ///
+    /// Unlike [`AIPlayer::analyze`], the moves are searched even if the opponent threatens to win
+    /// immediately.
+    pub fn evaluate_moves(&mut self, board: &Board, token: Token) -> Vec<(Column, Score)> {
+        let columns: Vec<Column> = board.ordered_moves().collect();
+        let root_moves = self.search_moves(board, &columns, token, SearchOptions::default());
+        sorted_scores(&root_moves)
+    }
+
+    /// Searches the given moves for `token` on the board, keeping the statistics of the search.
+    fn search_moves(
+        &mut self,
+        board: &Board,
+        columns: &[Column],
+        token: Token,
+        options: SearchOptions<'_>,
+    ) -> Vec<RootMove> {
+        let limits = Limits {
+            deadline: self.time_budget.map(|budget| Instant::now() + budget),
+            cancel: options.cancel,
+        };
+        let depth = self.max_depth(board);
+
+        let (root_moves, stats) = if self.threads > 1 {
+            let result = self.search_root_parallel(board, columns, depth, limits, token);
+            if let Some(progress) = options.progress {
+                progress(&result.1);
+            }
+            result
+        } else {
+            let mut search = Search::new(&mut self.ttable, &*self.evaluator);
+            let mut iterations =
+                search.deepen(board, columns, depth, limits, token, options.progress);
+            (iterations.pop().unwrap_or_default(), search.stats)
+        };
+        self.stats = stats;
+
+        root_moves
+    }
+
+    /// Searches the board, returning the sequence of moves expected to follow from it with best
+    /// play by both sides.
+    ///
+    /// See [`Analysis::pv`] for details.
+    pub fn principal_variation(&mut self, board: &Board) -> Vec<Column> {
+        self.analyze(board, board.current_player()).pv
+    }
+
+    /// Gets the statistics of the last search made by the player.
+    pub fn last_stats(&self) -> &SearchStats {
+        &self.stats
+    }
+
+    /// Solves the board exactly, searching to the end of the game.
+    ///
+    /// A win scores `22 - n` on the standard board, where `n` is the number of tokens the winner
+    /// has played when they win, and a loss scores the negation of the opponent's win. See
+    /// [`solver::Score`] for details. The solver keeps its transposition table between calls.
+    pub fn solve(&mut self, board: &Board) -> solver::Score {
+        self.solver.solve(board)
+    }
+
+    /// Solves whether the current player wins, draws or loses on the board with perfect play.
+    ///
+    /// This searches with the null window `[-1, 1]`, which is much faster than
+    /// [`AIPlayer::solve`] when the exact score is not needed.
+    pub fn solve_weak(&mut self, board: &Board) -> Outcome {
+        self.solver.wdl(board)
+    }
+
+    /// Solves the board exactly, returning the number of plies until the game is won with
+    /// perfect play, counting the winning move.
+    ///
+    /// The number is positive if the current player wins and negative if they lose. A board that
+    /// has already been won is lost in `0` plies, and `None` is returned if the game is a draw.
+    pub fn mate_in(&mut self, board: &Board) -> Option<i32> {
+        let score = self.solve(board);
+        if score == 0 {
+            return None;
         }
+
+        // The winner plays their winning move when the board holds `moves` tokens, the most that
+        // leaves them with `score` tokens in hand and is on their turn.
+        let ply = board.ply() as i32;
+        let winner_ply = if score > 0 { ply } else { ply + 1 };
+        let moves =
+            BOARD_SIZE as i32 + 1 - 2 * score.abs() - ((BOARD_SIZE as i32 + 1 - winner_ply) & 1);
+
+        let plies = moves - ply + 1;
+        Some(if score > 0 { plies } else { -plies })
     }
+
+    /// Clears the transposition tables of the player.
+    ///
+    /// The entries of the tables are keyed by position, so they stay valid between moves and
+    /// between games, but a table full of positions from an old game is of little use in a new
+    /// one, so the player clears them whenever a new game is started.
+    pub fn clear_table(&mut self) {
+        self.ttable.clear();
+        self.solver.reset();
+    }
+
+    /// Searches the root moves of the board, split between threads.
+    ///
+    /// The results are in the same order as a search on a single thread, and come from the
+    /// deepest iteration completed by every thread.
+    fn search_root_parallel(
+        &self,
+        board: &Board,
+        columns: &[Column],
+        depth: usize,
+        limits: Limits<'_>,
+        token: Token,
+    ) -> (Vec<RootMove>, SearchStats) {
+        let capacity = self.ttable.capacity();
+        let threads = self.threads.min(columns.len());
+        let chunks: Vec<Vec<Column>> = (0..threads)
+            .map(|i| columns.iter().copied().skip(i).step_by(threads).collect())
+            .collect();
+
+        // Each thread searches its chunk of the columns with a single transposition table made
+        // for this search, so the columns of a chunk share the positions they have in common,
+        // rather than each column starting from an empty table.
+        let board = *board;
+        let deadline = limits.deadline;
+        let search_chunk =
+            move |columns: Vec<Column>, evaluator: &E, cancel: Option<&AtomicBool>| {
+                let mut ttable = TranspositionTable::new(capacity);
+                let mut search = Search::new(&mut ttable, evaluator);
+                let limits = Limits { deadline, cancel };
+                let iterations = search.deepen(&board, &columns, depth, limits, token, None);
+                (iterations, search.stats)
+            };
+
+        #[cfg(feature = "rayon")]
+        let results: Vec<_> = chunks
+            .into_par_iter()
+            .map(|chunk| search_chunk(chunk, &self.evaluator, limits.cancel))
+            .collect();
+
+        // The threads cannot borrow the cancellation flag, so it is passed on to a flag of their
+        // own while waiting for them.
+        #[cfg(not(feature = "rayon"))]
+        let results: Vec<_> = {
+            let stop = Arc::new(AtomicBool::new(false));
+            let (sender, receiver) = mpsc::channel();
+
+            for (i, chunk) in chunks.into_iter().enumerate() {
+                let evaluator = Arc::clone(&self.evaluator);
+                let stop = Arc::clone(&stop);
+                let sender = sender.clone();
+                thread::spawn(move || {
+                    let result = search_chunk(chunk, &evaluator, Some(&stop));
+                    // The receiver outlives the threads unless the search panicked.
+                    let _ = sender.send((i, result));
+                });
+            }
+            drop(sender);
+
+            let mut results = Vec::with_capacity(threads);
+            while results.len() < threads {
+                match receiver.recv_timeout(Duration::from_millis(1)) {
+                    Ok(result) => results.push(result),
+                    Err(mpsc::RecvTimeoutError::Timeout) => {
+                        if limits.cancelled() {
+                            stop.store(true, Ordering::Relaxed);
+                        }
+                    }
+                    Err(mpsc::RecvTimeoutError::Disconnected) => panic!("search thread panicked"),
+                }
+            }
+
+            results.sort_by_key(|&(i, _)| i);
+            results.into_iter().map(|(_, result)| result).collect()
+        };
+
+        let mut stats = SearchStats::default();
+        let mut iterations: Vec<Vec<Vec<RootMove>>> = results
+            .into_iter()
+            .map(|(iterations, thread_stats)| {
+                stats.nodes += thread_stats.nodes;
+                stats.tt_hits += thread_stats.tt_hits;
+                stats.tt_stores += thread_stats.tt_stores;
+                stats.max_ply = stats.max_ply.max(thread_stats.max_ply);
+                stats.elapsed = stats.elapsed.max(thread_stats.elapsed);
+                iterations
+            })
+            .collect();
+
+        // Only compare moves searched to the same depth.
+        let completed = iterations.iter().map(Vec::len).min().unwrap_or(0);
+        stats.depth = completed;
+        if completed == 0 {
+            return (Vec::new(), stats);
+        }
+
+        let mut root_moves: Vec<RootMove> = iterations
+            .iter_mut()
+            .flat_map(|thread_iterations| thread_iterations.swap_remove(completed - 1))
+            .collect();
+        root_moves.sort_by_key(|root_move| columns.iter().position(|&c| c == root_move.column));
+        (root_moves, stats)
+    }
+}
+
+/// Gets the number of threads a new [`AIPlayer`] searches with.
+///
+/// With the `rayon` feature, the search is parallel by default and runs on the global thread pool
+/// of rayon.
+fn default_threads() -> usize {
+    #[cfg(feature = "rayon")]
+    return rayon::current_num_threads();
+
+    #[cfg(not(feature = "rayon"))]
+    return 1;
+}
+
+/// Gets the moves to search from the root of the board, for `token` to play.
+///
+/// If `token` cannot win immediately but the opponent threatens to, only the moves that block the
+/// threat are searched, since any other move loses.
+fn root_columns(board: &Board, token: Token) -> Vec<Column> {
+    if board.winning_move().is_none() {
+        let blocks: Vec<Column> = board.winning_moves(token.opponent()).collect();
+        if !blocks.is_empty() {
+            return blocks;
+        }
+    }
+
+    board.ordered_moves().collect()
+}
+
+/// Options for a search started with [`AIPlayer::decide_move_with`].
+#[derive(Default)]
+pub struct SearchOptions<'a> {
+    /// A flag that cancels the search once it is set.
+    ///
+    /// The first iteration of the search is always completed, so there is a move to play.
+    pub cancel: Option<&'a AtomicBool>,
+    /// A callback given the statistics of the search so far after each completed iteration.
+    ///
+    /// When searching with more than one thread, it is only called once the search is finished.
+    pub progress: Option<&'a mut dyn FnMut(&SearchStats)>,
+}
+
+/// The limits on how long a search may run for.
+#[derive(Clone, Copy, Default)]
+struct Limits<'a> {
+    /// The time at which the search is aborted, if any.
+    deadline: Option<Instant>,
+    /// A flag that aborts the search once it is set, if any.
+    cancel: Option<&'a AtomicBool>,
+}
+
+impl Limits<'_> {
+    /// Gets whether the cancellation flag is set.
+    fn cancelled(&self) -> bool {
+        self.cancel
+            .map_or(false, |cancel| cancel.load(Ordering::Relaxed))
+    }
+
+    /// Gets whether the search should be aborted.
+    fn reached(&self) -> bool {
+        self.cancelled()
+            || self
+                .deadline
+                .map_or(false, |deadline| Instant::now() >= deadline)
+    }
+}
+
+/// Gets whether playing in the column lets the opponent win with their next move.
+fn hands_win(board: &Board, column: Column) -> bool {
+    let mut board = *board;
+    board.make_move(column);
+    board.winner().is_none() && board.winning_move().is_some()
+}
+
+/// Gets every move with the best value, so a tie can be broken at random.
+fn best_moves(root_moves: Vec<RootMove>) -> Vec<RootMove> {
+    let mut best_moves: Vec<RootMove> = Vec::with_capacity(WIDTH as usize);
+    for root_move in root_moves {
+        let best_value = best_moves.first().map(|best_move| best_move.value);
+        match best_value.map(|best_value| root_move.value.cmp(&best_value)) {
+            None | Some(cmp::Ordering::Greater) => {
+                best_moves.clear();
+                best_moves.push(root_move);
+            }
+            Some(cmp::Ordering::Equal) => best_moves.push(root_move),
+            Some(cmp::Ordering::Less) => {}
+        }
+    }
+    best_moves
+}
+
+/// Gets the columns and values of the root moves, sorted from best to worst.
+fn sorted_scores(root_moves: &[RootMove]) -> Vec<(Column, Score)> {
+    let mut moves: Vec<(Column, Score)> = root_moves
+        .iter()
+        .map(|root_move| (root_move.column, root_move.value))
+        .collect();
+    moves.sort_by_key(|&(_, value)| Reverse(value));
+    moves
+}
+
+/// Statistics of a search.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SearchStats {
+    /// The number of positions searched.
+    pub nodes: u64,
+    /// The number of positions found in the transposition table.
+    pub tt_hits: u64,
+    /// The number of positions stored in the transposition table.
+    pub tt_stores: u64,
+    /// The depth of the deepest iteration that was completed.
+    pub depth: usize,
+    /// The greatest number of plies from the root reached by the search, including the plies
+    /// searched past its depth while a win was threatened.
+    pub max_ply: usize,
+    /// The time spent searching.
+    pub elapsed: Duration,
+}
+
+/// A searched move from the root of the search.
+#[derive(Default)]
+struct RootMove {
+    column: Column,
+    value: Score,
+    pv: Vec<Column>,
+}
+
+/// The result of analyzing a board.
+pub struct Analysis {
+    /// The best move found.
+    pub best: Column,
+    /// The score of the best move, from the perspective of the player making it.
+    pub score: Score,
+    /// The principal variation, the sequence of moves expected to follow from the board,
+    /// starting with the best move.
+    ///
+    /// The line may be cut short where the search relied on the transposition table.
+    pub pv: Vec<Column>,
+    /// The score of each move searched, sorted from best to worst.
+    ///
+    /// When the opponent threatens to win immediately, only the moves that block the threat are
+    /// searched.
+    pub moves: Vec<(Column, Score)>,
 }
 
-type TTable = HashMap<BitBoard, TTEntry>;
-type Score = i32;
+impl<E: Evaluator> Player for AIPlayer<E> {
+    fn decide_action(&mut self, board: &Board, token: Token) -> PlayerAction {
+        PlayerAction::Move(self.decide_move(board, token))
+    }
+
+    fn name(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.name)
+    }
+
+    fn new_game(&mut self) {
+        self.clear_table();
+    }
+}
+
+pub type Score = i32;
+
+/// A fixed-size transposition table, indexed by the low bits of the canonical key of the board.
+///
+/// When two positions share an index, the one searched to the greater depth is kept.
+///
+/// Entries are keyed by position, so they stay valid from one search to the next. Rather than
+/// overwriting every entry, clearing the table starts a new generation, and entries stored in
+/// earlier generations are ignored and replaced as if their slots were empty.
+struct TranspositionTable {
+    /// The entries of the table, along with the generation they were stored in.
+    entries: Vec<Option<(u8, TTEntry)>>,
+    /// The mask of the bits of the key used as an index.
+    mask: usize,
+    generation: u8,
+}
+
+impl TranspositionTable {
+    /// Creates an empty table with room for `capacity` entries, rounded down to a power of two.
+    fn new(capacity: usize) -> TranspositionTable {
+        let capacity = match capacity {
+            0 => 1,
+            capacity => 1 << (usize::BITS - 1 - capacity.leading_zeros()),
+        };
+
+        let mut entries = Vec::new();
+        entries.resize_with(capacity, || None);
+
+        TranspositionTable {
+            entries,
+            mask: capacity - 1,
+            generation: 0,
+        }
+    }
+
+    /// Creates an empty table using at most `bytes` of memory for its entries.
+    fn with_memory(bytes: usize) -> TranspositionTable {
+        TranspositionTable::new(bytes / mem::size_of::<Option<(u8, TTEntry)>>())
+    }
+
+    /// Gets the number of entries the table can hold.
+    fn capacity(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Removes all entries from the table.
+    fn clear(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+
+        // Entries from the generation before the counter wrapped around would be taken as current.
+        if self.generation == 0 {
+            self.entries.iter_mut().for_each(|slot| *slot = None);
+        }
+    }
+
+    /// Gets the entry for the given key, if it is in the table.
+    fn probe(&self, key: u64) -> Option<&TTEntry> {
+        match &self.entries[key as usize & self.mask] {
+            Some((generation, entry)) if *generation == self.generation && entry.key == key => {
+                Some(entry)
+            }
+            _ => None,
+        }
+    }
+
+    /// Stores an entry, unless its slot holds an entry for another position searched deeper.
+    ///
+    /// Returns whether the entry was stored.
+    fn store(&mut self, entry: TTEntry) -> bool {
+        let slot = &mut self.entries[entry.key as usize & self.mask];
+
+        match slot {
+            Some((generation, old))
+                if *generation == self.generation
+                    && old.key != entry.key
+                    && old.depth > entry.depth =>
+            {
+                false
+            }
+            _ => {
+                *slot = Some((self.generation, entry));
+                true
+            }
+        }
+    }
+}
 
 struct TTEntry {
+    /// The full key of the position, to tell apart positions sharing a slot.
+    key: u64,
     depth: usize,
     value: Score,
     flag: TTFlag,
+    /// The move that produced the value.
+    best_move: Column,
 }
 
 enum TTFlag {
@@ -90,154 +879,1320 @@ enum TTFlag {
     Upperbound,
 }
 
-fn negamax(
-    ttable: &mut TTable,
-    mut board: PeekableBoard,
-    depth: usize,
-    mut a: Score,
-    mut b: Score,
-    side: Token,
-) -> Score {
-    let a_orig = a;
+/// The initial distance from the guessed value to either side of an aspiration window.
+///
+/// This is a few times the value of a threat in the default evaluator, which is more than the
+/// value of a move usually changes by from one iteration to the next.
+const ASPIRATION_DELTA: Score = 100;
+
+/// The number of plies a search can reach from its root, including the root itself.
+const MAX_DEPTH: usize = BOARD_SIZE as usize + 1;
+
+/// The enhancements of the search, which are all used in play but can be turned off to measure
+/// what each of them saves.
+#[derive(Clone, Copy)]
+struct Enhancements {
+    /// Searching the moves from the center outwards, rather than from left to right.
+    center_first: bool,
+    killers: bool,
+    tt_move: bool,
+    history: bool,
+    /// Searching the moves after the first with a null window.
+    pvs: bool,
+    /// Searching the moves of each iteration with a window around their previous values.
+    aspiration: bool,
+}
+
+impl Default for Enhancements {
+    fn default() -> Enhancements {
+        Enhancements {
+            center_first: true,
+            killers: true,
+            tt_move: true,
+            history: true,
+            pvs: true,
+            aspiration: true,
+        }
+    }
+}
+
+/// The state of a search from a single root position.
+struct Search<'a, E: Evaluator> {
+    ttable: &'a mut TranspositionTable,
+    evaluator: &'a E,
+    /// Moves that recently caused a beta cutoff, indexed by ply from the root.
+    killers: [[Option<Column>; 2]; MAX_DEPTH],
+    /// Scores of the moves that caused beta cutoffs anywhere in the tree, indexed by the player
+    /// to move and the column.
+    history: [[u32; WIDTH as usize]; 2],
+    /// Triangular table of principal variations, where row `ply` holds the best line found
+    /// for the latest node searched at that ply in `pv[ply][ply..pv_len[ply]]`.
+    pv: [[Column; MAX_DEPTH]; MAX_DEPTH],
+    pv_len: [usize; MAX_DEPTH],
+    /// The limits after which the search is aborted.
+    limits: Limits<'a>,
+    aborted: bool,
+    stats: SearchStats,
+    enhancements: Enhancements,
+}
+
+impl<'a, E: Evaluator> Search<'a, E> {
+    fn new(ttable: &'a mut TranspositionTable, evaluator: &'a E) -> Search<'a, E> {
+        Search {
+            ttable,
+            evaluator,
+            killers: [[None; 2]; MAX_DEPTH],
+            history: [[0; WIDTH as usize]; 2],
+            pv: [[0; MAX_DEPTH]; MAX_DEPTH],
+            pv_len: [0; MAX_DEPTH],
+            limits: Limits::default(),
+            aborted: false,
+            stats: SearchStats::default(),
+            enhancements: Enhancements::default(),
+        }
+    }
+
+    /// Searches the given moves from the root position with iterative deepening, returning the
+    /// results of each completed iteration.
+    ///
+    /// Each iteration searches one ply deeper than the last, and the transposition table, killer
+    /// moves and history scores it leaves behind improve the move ordering of the next. Once the deadline
+    /// passes or the search is cancelled, the current iteration is abandoned. The `progress`
+    /// callback is given the statistics of the search after each completed iteration.
+    fn deepen(
+        &mut self,
+        board: &Board,
+        columns: &[Column],
+        depth: usize,
+        limits: Limits<'a>,
+        token: Token,
+        mut progress: Option<&mut dyn FnMut(&SearchStats)>,
+    ) -> Vec<Vec<RootMove>> {
+        let started = Instant::now();
+        let mut iterations: Vec<Vec<RootMove>> = Vec::with_capacity(depth);
+
+        for depth in 1..=depth {
+            let mut root_moves = Vec::with_capacity(columns.len());
+
+            for (i, &column) in columns.iter().enumerate() {
+                let guess = iterations
+                    .last()
+                    .filter(|_| self.enhancements.aspiration)
+                    .map(|root_moves| root_moves[i].value);
+                let root_move = self.root_move(board, column, depth, guess, token);
+                if self.aborted {
+                    self.stats.elapsed = started.elapsed();
+                    return iterations;
+                }
+                root_moves.push(root_move);
+            }
+
+            iterations.push(root_moves);
+            self.stats.depth = depth;
+            self.stats.elapsed = started.elapsed();
+            if let Some(progress) = progress.as_mut() {
+                progress(&self.stats);
+            }
+
+            // The first iteration is always completed, so there is a move to play.
+            self.limits = limits;
+
+            // Cutoffs in deeper iterations say more about the moves, so older scores fade.
+            for score in self.history.iter_mut().flatten() {
+                *score /= 2;
+            }
+        }
+
+        iterations
+    }
+
+    /// Searches a move from the root position to the given depth.
+    ///
+    /// With a `guess` at the value of the move, such as its value in the previous iteration, the
+    /// search starts with an aspiration window around the guess. When the value falls outside
+    /// the window, the side it fell on is widened and the move is searched again.
+    fn root_move(
+        &mut self,
+        board: &Board,
+        column: Column,
+        depth: usize,
+        guess: Option<Score>,
+        token: Token,
+    ) -> RootMove {
+        let mut board = *board;
+
+        let mut delta = ASPIRATION_DELTA;
+        let (mut a, mut b) = match guess {
+            Some(guess) => (guess.saturating_sub(delta), guess.saturating_add(delta)),
+            None => (Score::MIN, Score::MAX),
+        };
+
+        let value = loop {
+            let value = self
+                .negamax(
+                    board.peekable(column),
+                    depth,
+                    1,
+                    b.saturating_neg(),
+                    a.saturating_neg(),
+                    token.opponent(),
+                )
+                .saturating_neg();
+
+            delta = delta.saturating_mul(4);
+            if self.aborted {
+                break value;
+            } else if value <= a && a > Score::MIN {
+                a = value.saturating_sub(delta);
+            } else if value >= b && b < Score::MAX {
+                b = value.saturating_add(delta);
+            } else {
+                break value;
+            }
+        };
+
+        let mut pv = Vec::with_capacity(self.pv_len[1]);
+        pv.push(column);
+        pv.extend_from_slice(&self.pv[1][1..self.pv_len[1]]);
+
+        RootMove { column, value, pv }
+    }
+
+    fn negamax(
+        &mut self,
+        mut board: PeekableBoard,
+        depth: usize,
+        ply: usize,
+        mut a: Score,
+        mut b: Score,
+        side: Token,
+    ) -> Score {
+        let a_orig = a;
+        self.pv_len[ply] = ply;
+        self.count_node(ply);
+
+        if self.should_stop() {
+            return 0;
+        }
+
+        // Mirrored positions share an entry, with the best move stored for the canonical one.
+        let (key, mirrored) = board.canonical_key();
+        let reflect = |column: Column| Board::reflect_column(column, mirrored);
+
+        // Look up board in transposition table. The best move of a shallower search is still
+        // worth trying first.
+        let mut tt_move = None;
+        if let Some(entry) = self.ttable.probe(key) {
+            self.stats.tt_hits += 1;
+            if self.enhancements.tt_move {
+                tt_move = Some(reflect(entry.best_move));
+            }
+
+            if entry.depth >= depth {
+                match entry.flag {
+                    TTFlag::Exact => return entry.value,
+                    TTFlag::Lowerbound => a = a.max(entry.value),
+                    TTFlag::Upperbound => b = b.min(entry.value),
+                }
+
+                if a >= b {
+                    return entry.value;
+                }
+            }
+        }
+
+        let (moves, len_moves) = self.order_moves(&board, ply, side, tt_move);
+
+        // Wins found with more of the search left are closer, so they are preferred to distant
+        // wins and distant losses are preferred to close losses. The adjustment is at most the
+        // number of cells on the board, far less than the gap to the value of any unfinished
+        // board.
+        if let Some(winner) = board.winner() {
+            let value = self.evaluator.win_value() + depth as Score;
+            return if winner == side { value } else { -value };
+        }
 
-    let position_code = board.position_code();
+        // If the board is full at this point, the game is a draw.
+        if board.is_full() {
+            return 0;
+        }
+
+        // If reached max depth, settle any immediate wins before using the heuristic value.
+        if depth == 0 {
+            return self.quiesce(board, ply, side, 0);
+        }
+
+        // The first move is expected to be the best, so the rest are searched with a null window
+        // that only proves they are no better, and searched again if one turns out to be.
+        let mut value = Score::MIN;
+        let mut best_move = moves[0];
+        for (i, &column) in moves[..len_moves].iter().enumerate() {
+            let null_window = i > 0 && self.enhancements.pvs;
+            let mut child_value = Score::MIN;
+            if null_window {
+                child_value = self
+                    .negamax(
+                        board.peek(column),
+                        depth.saturating_sub(1),
+                        ply + 1,
+                        a.saturating_add(1).saturating_neg(),
+                        a.saturating_neg(),
+                        side.opponent(),
+                    )
+                    .saturating_neg();
+            }
+            if !null_window || (child_value > a && child_value < b && !self.aborted) {
+                child_value = self
+                    .negamax(
+                        board.peek(column),
+                        depth.saturating_sub(1),
+                        ply + 1,
+                        b.saturating_neg(),
+                        a.saturating_neg(),
+                        side.opponent(),
+                    )
+                    .saturating_neg();
+            }
+
+            // The value of an aborted search is meaningless, so it must not be stored.
+            if self.aborted {
+                return 0;
+            }
 
-    // Look up board in transposition table.
-    match ttable.get(&position_code) {
-        Some(entry) if entry.depth >= depth => {
-            match entry.flag {
-                TTFlag::Exact => return entry.value,
-                TTFlag::Lowerbound => a = a.max(entry.value),
-                TTFlag::Upperbound => b = b.min(entry.value),
+            if child_value > value {
+                value = child_value;
+                best_move = column;
+                self.update_pv(ply, column);
             }
+            a = a.max(value);
 
             if a >= b {
-                return entry.value;
+                self.store_killer(ply, column);
+                self.store_history(side, column, depth);
+                break;
             }
         }
-        _ => {}
+
+        // Store board in transposition table.
+        let flag = if value <= a_orig {
+            TTFlag::Upperbound
+        } else if value >= b {
+            TTFlag::Lowerbound
+        } else {
+            TTFlag::Exact
+        };
+        let entry = TTEntry {
+            key,
+            depth,
+            value,
+            flag,
+            best_move: reflect(best_move),
+        };
+        if self.ttable.store(entry) {
+            self.stats.tt_stores += 1;
+        }
+
+        value
     }
 
-    let mut legal_moves = board.legal_moves().peekable();
+    /// Searches past the maximum depth while a player can win immediately, so the heuristic is
+    /// not used on a board where the outcome is decided by the next few moves.
+    ///
+    /// Wins found `beyond` plies past the maximum depth are valued less than those within it.
+    /// Only forced moves are searched, so there is nothing for a window to prune. The board
+    /// itself has already been counted as a node by `negamax`.
+    fn quiesce(
+        &mut self,
+        mut board: PeekableBoard,
+        ply: usize,
+        side: Token,
+        beyond: Score,
+    ) -> Score {
+        // The previous player did not win, so if the board is full it is a draw.
+        if board.is_full() {
+            return 0;
+        }
+
+        let win = self.evaluator.win_value();
 
-    // If reached max depth or at a terminal board state, return heuristic value.
-    {
-        let winner = board.winner();
-        let is_full = legal_moves.peek().is_none();
+        if board.winning_move().is_some() {
+            return win - (beyond + 1);
+        }
 
-        if depth == 0 || (winner.is_some() || is_full) {
-            return heuristic_value(&board, side, winner, is_full);
+        let mut threats = board.winning_moves(side.opponent());
+        match (threats.next(), threats.next()) {
+            // Only one threat can be blocked, so the opponent wins with the other.
+            (Some(_), Some(_)) => -(win - (beyond + 2)),
+            // The threat must be blocked, so there is only one move worth searching.
+            (Some(block), None) => {
+                self.count_node(ply + 1);
+                -self.quiesce(board.peek(block), ply + 1, side.opponent(), beyond + 1)
+            }
+            _ => self.evaluator.evaluate(&board, side),
         }
     }
 
-    let mut value = Score::MIN;
-    for column in legal_moves {
-        value = value.max(
-            negamax(
-                ttable,
-                board.peek(column),
-                depth.saturating_sub(1),
-                b.saturating_neg(),
-                a.saturating_neg(),
-                side.opponent(),
-            )
-            .saturating_neg(),
-        );
-        a = a.max(value);
+    /// Counts a position searched at the given ply.
+    fn count_node(&mut self, ply: usize) {
+        self.stats.nodes += 1;
+        self.stats.max_ply = self.stats.max_ply.max(ply);
+    }
+
+    /// Checks whether the deadline has passed or the search was cancelled, aborting the search if
+    /// so.
+    fn should_stop(&mut self) -> bool {
+        self.aborted |= self.limits.reached();
+        self.aborted
+    }
 
-        if a >= b {
-            break;
+    /// Gets the legal moves of the board in the order they should be searched.
+    ///
+    /// The best move from the transposition table is tried first, then the killer moves for the
+    /// ply, followed by the rest by their history scores for the side to move, from the center
+    /// outwards when the scores are equal.
+    fn order_moves(
+        &self,
+        board: &Board,
+        ply: usize,
+        side: Token,
+        tt_move: Option<Column>,
+    ) -> ([Column; WIDTH as usize], usize) {
+        let mut moves = [0; WIDTH as usize];
+        let mut len_moves = 0;
+
+        let killers = if self.enhancements.killers {
+            &self.killers[ply][..]
+        } else {
+            &[]
+        };
+        for &column in tt_move.iter().chain(killers.iter().flatten()) {
+            if board.has_space(column) && !moves[..len_moves].contains(&column) {
+                moves[len_moves] = column;
+                len_moves += 1;
+            }
         }
+
+        let first_quiet = len_moves;
+        for column in board.ordered_moves() {
+            if !moves[..len_moves].contains(&column) {
+                moves[len_moves] = column;
+                len_moves += 1;
+            }
+        }
+
+        if !self.enhancements.center_first {
+            moves[first_quiet..len_moves].sort_unstable();
+        }
+
+        // The sort is stable, so moves with equal scores keep their order from the center.
+        if self.enhancements.history {
+            let history = &self.history[history_index(side)];
+            moves[first_quiet..len_moves].sort_by_key(|&column| Reverse(history[column as usize]));
+        }
+
+        (moves, len_moves)
     }
 
-    // Store board in transposition table.
-    let flag = if value <= a_orig {
-        TTFlag::Upperbound
-    } else if value >= b {
-        TTFlag::Lowerbound
-    } else {
-        TTFlag::Exact
-    };
-    let entry = TTEntry { depth, value, flag };
-    ttable.insert(position_code, entry);
+    /// Sets the principal variation at the given ply to `column` followed by the line of the
+    /// next ply.
+    fn update_pv(&mut self, ply: usize, column: Column) {
+        let child_len = self.pv_len[ply + 1];
+
+        let (rows, child_rows) = self.pv.split_at_mut(ply + 1);
+        let row = &mut rows[ply];
+        row[ply] = column;
+        row[ply + 1..child_len].copy_from_slice(&child_rows[0][ply + 1..child_len]);
+
+        self.pv_len[ply] = child_len;
+    }
+
+    /// Records a move that caused a beta cutoff at the given ply.
+    fn store_killer(&mut self, ply: usize, column: Column) {
+        let killers = &mut self.killers[ply];
+
+        if killers[0] != Some(column) {
+            killers[1] = killers[0];
+            killers[0] = Some(column);
+        }
+    }
 
-    value
+    /// Records a move that caused a beta cutoff with the given depth left, where cutoffs with
+    /// more of the search below them score more.
+    fn store_history(&mut self, side: Token, column: Column, depth: usize) {
+        let score = &mut self.history[history_index(side)][column as usize];
+        *score = score.saturating_add((depth * depth) as u32);
+    }
+}
+
+/// Gets the index of the player in the history table.
+fn history_index(side: Token) -> usize {
+    side.player() as usize - 1
 }
 
-fn heuristic_value(board: &Board, side: Token, winner: Option<Token>, is_full: bool) -> Score {
-    const WIN: Score = 10_000;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Searches the board to the given depth with only the given enhancements, returning the
+    /// values of the moves in each iteration and the number of nodes searched.
+    fn search_with(
+        board: &Board,
+        depth: usize,
+        enhancements: Enhancements,
+    ) -> (Vec<Vec<Score>>, u64) {
+        let mut ttable = TranspositionTable::with_memory(1 << 20);
+        let evaluator = DefaultEvaluator::default();
+        let mut search = Search::new(&mut ttable, &evaluator);
+        search.enhancements = enhancements;
 
-    if let Some(winner) = winner {
-        return if winner == side { WIN } else { -WIN };
+        let columns: Vec<Column> = board.ordered_moves().collect();
+        let token = board.current_player();
+        let iterations = search.deepen(board, &columns, depth, Limits::default(), token, None);
+        let values = iterations
+            .iter()
+            .map(|root_moves| root_moves.iter().map(|root_move| root_move.value).collect())
+            .collect();
+
+        (values, search.stats.nodes)
     }
 
-    // If the board is full at this point, the game is a draw.
-    if is_full {
-        return 0;
+    /// Searches the board to the given depth with only the given enhancements, returning the
+    /// number of nodes searched.
+    fn nodes_with(board: &Board, depth: usize, enhancements: Enhancements) -> u64 {
+        search_with(board, depth, enhancements).1
     }
 
-    let mut total_score = 0;
+    /// The search with none of the enhancements that order the moves.
+    const UNORDERED: Enhancements = Enhancements {
+        center_first: false,
+        killers: false,
+        tt_move: false,
+        history: false,
+        pvs: false,
+        aspiration: false,
+    };
 
-    for column in 0..WIDTH {
-        for row in 0..HEIGHT {
-            if let Some(token) = board.token_at(row, column) {
-                const DIRECTION: [(ColumnDiff, ColumnDiff); 4] = [(1, 0), (1, 1), (0, 1), (-1, 1)];
+    /// The search with only the enhancement of searching from the center first.
+    const CENTER_FIRST: Enhancements = Enhancements {
+        center_first: true,
+        ..UNORDERED
+    };
 
-                for &(i, j) in &DIRECTION {
-                    let forward = get_length(board, (row, column), (i, j), token);
-                    let backward = get_length(board, (row, column), (-i, -j), token);
+    #[test]
+    fn center_first_searches_fewer_nodes() {
+        let board = Board::from_move_string("4453").unwrap();
 
-                    let current_len = forward.0 + backward.0 + 1;
-                    let possible_len = forward.1 + backward.1 + 1;
+        assert!(nodes_with(&board, 5, CENTER_FIRST) < nodes_with(&board, 5, UNORDERED));
+    }
 
-                    if possible_len >= 4 {
-                        let score = 10 * Score::from(current_len);
-                        if side == token {
-                            total_score += score;
-                        } else {
-                            total_score -= score;
-                        }
-                    }
+    #[test]
+    fn killers_search_fewer_nodes() {
+        let board = Board::from_move_string("4453").unwrap();
+        let killers = Enhancements {
+            killers: true,
+            ..CENTER_FIRST
+        };
+
+        assert!(nodes_with(&board, 5, killers) < nodes_with(&board, 5, CENTER_FIRST));
+    }
+
+    #[test]
+    fn principal_variation_starts_with_the_best_move() {
+        for moves in &["", "4453", "4455", "141576"] {
+            let board = Board::from_move_string(moves).unwrap();
+            let mut ai = AIPlayer::with_depth(5);
+
+            let analysis = ai.analyze(&board, board.current_player());
+            assert_eq!(analysis.pv.first(), Some(&analysis.best), "{}", moves);
+
+            let mut line = board;
+            for &column in &analysis.pv {
+                assert!(line.is_legal(column), "{} then {:?}", moves, analysis.pv);
+                line.make_move(column);
+            }
+        }
+    }
+
+    #[test]
+    fn parallel_search_gives_the_values_of_a_single_thread() {
+        for moves in ["", "4453", "3344"] {
+            let board = Board::from_move_string(moves).unwrap();
+            let token = board.current_player();
+            let expected = AIPlayer::with_depth(6)
+                .with_threads(1)
+                .evaluate_moves(&board, token);
+
+            for threads in [2, 3, 7] {
+                let mut ai = AIPlayer::with_depth(6).with_threads(threads);
+                assert_eq!(
+                    ai.evaluate_moves(&board, token),
+                    expected,
+                    "moves {:?}",
+                    moves
+                );
+                assert_eq!(ai.last_stats().depth, 6);
+            }
+        }
+    }
+
+    #[test]
+    fn each_iteration_gives_the_values_of_a_fixed_depth_search() {
+        let board = Board::from_move_string("4453").unwrap();
+        let token = board.current_player();
+        let columns: Vec<Column> = board.ordered_moves().collect();
+        let evaluator = DefaultEvaluator::default();
+        let (iterations, _) = search_with(&board, 7, Enhancements::default());
+
+        for (depth, values) in (1..).zip(iterations) {
+            let mut ttable = TranspositionTable::with_memory(1 << 20);
+            let mut search = Search::new(&mut ttable, &evaluator);
+            let expected: Vec<Score> = columns
+                .iter()
+                .map(|&column| search.root_move(&board, column, depth, None, token).value)
+                .collect();
+
+            assert_eq!(values, expected, "depth {}", depth);
+        }
+    }
+
+    #[test]
+    fn tiny_time_budget_plays_a_legal_move_promptly() {
+        let board = Board::from_move_string("4453").unwrap();
+
+        for threads in [1, 3] {
+            // Searching to this depth would take far too long without the budget.
+            let mut ai = AIPlayer::with_depth(30)
+                .with_threads(threads)
+                .with_time_budget(Duration::from_millis(1));
+            let started = Instant::now();
+            let column = ai.decide_move(&board, board.current_player());
+
+            assert!(started.elapsed() < Duration::from_secs(1));
+            assert!(board.is_legal(column));
+            assert!(ai.last_stats().depth < 30);
+        }
+    }
+
+    #[test]
+    fn tt_move_searches_fewer_nodes() {
+        let board = Board::from_move_string("4453").unwrap();
+        let tt_move = Enhancements {
+            tt_move: true,
+            ..CENTER_FIRST
+        };
+
+        assert!(nodes_with(&board, 5, tt_move) < nodes_with(&board, 5, CENTER_FIRST));
+    }
+
+    #[test]
+    fn tt_move_is_tried_first() {
+        let board = Board::from_move_string("4453").unwrap();
+        let mut ttable = TranspositionTable::with_memory(1 << 10);
+        let evaluator = DefaultEvaluator::default();
+        let mut search = Search::new(&mut ttable, &evaluator);
+        search.killers[0] = [Some(2), Some(4)];
+        search.history[history_index(Token::Player1)][5] = 100;
+
+        let (moves, len_moves) = search.order_moves(&board, 0, Token::Player1, Some(0));
+        assert_eq!(moves[..len_moves], [0, 2, 4, 5, 3, 1, 6]);
+    }
+
+    #[test]
+    fn history_searches_fewer_nodes() {
+        let board = Board::from_move_string("4453").unwrap();
+        let history = Enhancements {
+            history: true,
+            ..CENTER_FIRST
+        };
+
+        assert!(nodes_with(&board, 5, history) < nodes_with(&board, 5, CENTER_FIRST));
+    }
+
+    #[test]
+    fn null_windows_give_the_values_of_a_full_window() {
+        let no_pvs = Enhancements {
+            pvs: false,
+            ..Enhancements::default()
+        };
+
+        let (mut nodes, mut full_window_nodes) = (0, 0);
+        for moves in ["", "4453", "3344", "444455", "7654", "2255"] {
+            let board = Board::from_move_string(moves).unwrap();
+            let (values, pvs_nodes) = search_with(&board, 7, Enhancements::default());
+            let (expected, no_pvs_nodes) = search_with(&board, 7, no_pvs);
+
+            assert_eq!(values, expected, "moves {:?}", moves);
+            nodes += pvs_nodes;
+            full_window_nodes += no_pvs_nodes;
+        }
+        // A null window that fails high searches the move again, so a few positions take more
+        // nodes, but fewer are searched overall.
+        assert!(nodes < full_window_nodes);
+    }
+
+    /// Creates an entry for the key, searched to the given depth.
+    fn entry(key: u64, depth: usize) -> TTEntry {
+        TTEntry {
+            key,
+            depth,
+            value: depth as Score,
+            flag: TTFlag::Exact,
+            best_move: 3,
+        }
+    }
+
+    #[test]
+    fn table_keeps_the_deeper_of_colliding_entries() {
+        let mut ttable = TranspositionTable::new(16);
+        assert!(ttable.store(entry(1, 5)));
+
+        // The key 17 shares the slot of the key 1.
+        assert!(!ttable.store(entry(17, 4)));
+        assert_eq!(ttable.probe(1).unwrap().depth, 5);
+        assert!(ttable.probe(17).is_none());
+
+        assert!(ttable.store(entry(17, 6)));
+        assert_eq!(ttable.probe(17).unwrap().depth, 6);
+        assert!(ttable.probe(1).is_none());
+
+        // An entry for the same position always replaces the old one.
+        assert!(ttable.store(entry(17, 2)));
+        assert_eq!(ttable.probe(17).unwrap().depth, 2);
+    }
+
+    #[test]
+    fn cleared_table_replaces_old_entries() {
+        let mut ttable = TranspositionTable::new(16);
+        ttable.store(entry(1, 5));
+        ttable.clear();
+
+        assert!(ttable.probe(1).is_none());
+        assert!(ttable.store(entry(17, 1)));
+        assert_eq!(ttable.probe(17).unwrap().depth, 1);
+    }
+
+    #[test]
+    fn table_fits_in_its_memory() {
+        let entry_size = mem::size_of::<Option<(u8, TTEntry)>>();
+        for &bytes in &[0, 1000, 1 << 20, (1 << 20) + 1, 3 << 20] {
+            let ttable = TranspositionTable::with_memory(bytes);
+            let capacity = ttable.capacity();
+
+            assert!(capacity.is_power_of_two());
+            assert!(capacity == 1 || capacity * entry_size <= bytes);
+            assert!(capacity * entry_size * 2 > bytes);
+        }
+    }
+
+    /// Positions with their exact scores, from a win to a loss for the side to move.
+    const SOLVED: [(&str, solver::Score); 4] = [
+        ("3723651443715621542266323342", 6),
+        ("7422341735647741166133573473242566", 1),
+        ("23163416124767223154467471272416755633", 0),
+        ("2252576253462244111563365343671351441", -1),
+    ];
+
+    #[test]
+    fn solve_gives_exact_scores() {
+        let mut ai = AIPlayer::new(Difficulty::Easy);
+        for &(moves, score) in SOLVED.iter() {
+            let board = Board::from_move_string(moves).unwrap();
+            assert_eq!(ai.solve(&board), score, "moves {:?}", moves);
+        }
+    }
+
+    #[test]
+    fn solve_weak_matches_the_sign_of_the_score() {
+        let mut ai = AIPlayer::new(Difficulty::Easy);
+        for &(moves, _) in SOLVED.iter() {
+            let board = Board::from_move_string(moves).unwrap();
+            let outcome = match ai.solve(&board).cmp(&0) {
+                cmp::Ordering::Greater => Outcome::Win,
+                cmp::Ordering::Equal => Outcome::Draw,
+                cmp::Ordering::Less => Outcome::Loss,
+            };
+
+            ai.clear_table();
+            assert_eq!(ai.solve_weak(&board), outcome, "moves {:?}", moves);
+        }
+    }
+
+    #[test]
+    fn mirrored_boards_share_entries() {
+        let board = Board::from_move_string("4453").unwrap();
+        let mirror = board.mirror();
+        let token = board.current_player();
+
+        let (key, mirrored) = board.canonical_key();
+        assert_eq!(mirror.canonical_key(), (key, !mirrored));
+
+        let mut ai = AIPlayer::with_depth(5).with_threads(1);
+        let moves = ai.evaluate_moves(&board, token);
+        let nodes = ai.last_stats().nodes;
+
+        // The mirror is searched with the entries of the board, so it needs fewer nodes.
+        let mirror_moves = ai.evaluate_moves(&mirror, token);
+        assert!(ai.last_stats().nodes < nodes);
+
+        // Moves with equal values are in no particular order.
+        let mut expected: Vec<(Column, Score)> = moves
+            .iter()
+            .map(|&(column, value)| (Board::reflect_column(column, true), value))
+            .collect();
+        let mut mirror_moves = mirror_moves;
+        expected.sort_unstable();
+        mirror_moves.sort_unstable();
+        assert_eq!(mirror_moves, expected);
+    }
+
+    #[test]
+    fn mirrored_entries_give_mirrored_moves() {
+        let board = Board::from_move_string("4453").unwrap();
+        let mut ai = AIPlayer::with_depth(5).with_threads(1);
+        ai.evaluate_moves(&board, board.current_player());
+
+        for column in board.legal_moves() {
+            let mut child = board;
+            child.make_move(column);
+            let mut mirror_child = board.mirror();
+            mirror_child.make_move(Board::reflect_column(column, true));
+
+            let (key, mirrored) = child.canonical_key();
+            let best_move = ai.ttable.probe(key).unwrap().best_move;
+            assert_eq!(mirror_child.canonical_key(), (key, !mirrored));
+            assert!(child.is_legal(Board::reflect_column(best_move, mirrored)));
+            assert!(mirror_child.is_legal(Board::reflect_column(best_move, !mirrored)));
+        }
+    }
+
+    #[test]
+    fn faster_wins_are_preferred() {
+        // The first player wins at once up the first column, or in three plies by making two
+        // threats on the bottom row.
+        let board = Board::from_move_string("4455141517").unwrap();
+
+        for seed in 0..5 {
+            let mut ai = AIPlayer::with_seed(Difficulty::Medium, seed);
+            assert_eq!(ai.decide_move(&board, Token::Player1), 0);
+        }
+
+        let mut ai = AIPlayer::new(Difficulty::Medium);
+        let moves = ai.evaluate_moves(&board, Token::Player1);
+        let value = |column| moves.iter().find(|&&(c, _)| c == column).unwrap().1;
+        assert!(value(0) > value(2));
+        assert!(value(2) > eval::WIN);
+    }
+
+    #[test]
+    fn name_defaults_to_the_difficulty() {
+        assert_eq!(AIPlayer::new(Difficulty::Hard).name(), "AI (Hard)");
+        assert_eq!(AIPlayer::with_depth(5).name(), "AI (depth 5)");
+
+        let ai = AIPlayer::new(Difficulty::Easy)
+            .with_name("Bob")
+            .with_weights(HeuristicWeights::default());
+        assert_eq!(ai.name(), "Bob");
+    }
+
+    #[test]
+    fn moves_that_hand_the_opponent_a_win_are_avoided() {
+        // The second player threatens to complete the second row in the center column, so the
+        // first player must not play there, even though it is the center.
+        let board = Board::from_move_string("56751627").unwrap();
+        assert!(hands_win(&board, 3));
+
+        for seed in 0..10 {
+            let mut ai = AIPlayer::with_seed(Difficulty::Easy, seed);
+            assert_ne!(ai.decide_move(&board, Token::Player1), 3);
+        }
+
+        let mut ai = AIPlayer::with_depth(1);
+        let analysis = ai.analyze(&board, Token::Player1);
+        assert_ne!(analysis.best, 3);
+        assert!(!hands_win(&board, analysis.best));
+    }
+
+    #[test]
+    fn immediate_threats_are_blocked() {
+        // The second player threatens to complete the bottom row in the third column.
+        let board = Board::from_move_string("141576").unwrap();
+        assert_eq!(root_columns(&board, Token::Player1), [2]);
+
+        for seed in 0..10 {
+            let mut ai = AIPlayer::with_seed(Difficulty::Easy, seed);
+            assert_eq!(ai.decide_move(&board, Token::Player1), 2);
+        }
+
+        let mut ai = AIPlayer::with_depth(1);
+        assert_eq!(ai.decide_move(&board, Token::Player1), 2);
+
+        // A win of its own up the first column is taken rather than the block.
+        let board = Board::from_move_string("14157612").unwrap();
+        let mut ai = AIPlayer::new(Difficulty::Easy);
+        assert_eq!(ai.decide_move(&board, Token::Player1), 0);
+    }
+
+    #[test]
+    fn players_with_the_same_seed_make_the_same_moves() {
+        fn assert_send<T: Send>() {}
+        assert_send::<AIPlayer>();
+
+        // Several of the moves on these boards are equally good at this depth.
+        let boards = ["", "4", "44", "4453", "1"];
+
+        for seed in 0..5 {
+            let mut a = AIPlayer::with_seed(Difficulty::Easy, seed);
+            let mut b = AIPlayer::with_seed(Difficulty::Easy, seed);
+
+            for moves in &boards {
+                let board = Board::from_move_string(moves).unwrap();
+                let token = board.current_player();
+                assert_eq!(a.decide_move(&board, token), b.decide_move(&board, token));
+            }
+        }
+    }
+
+    #[test]
+    fn players_with_the_same_seed_play_the_same_game() {
+        let mut a = AIPlayer::with_seed(Difficulty::Easy, 7);
+        let mut b = AIPlayer::with_seed(Difficulty::Easy, 7);
+        let script = [3, 3, 2, 4, 1, 5, 0, 6, 2, 4];
+
+        let mut board = Board::new();
+        for &column in &script {
+            let token = board.current_player();
+            let decided = a.decide_move(&board, token);
+            assert_eq!(
+                b.decide_move(&board, token),
+                decided,
+                "{}",
+                board.to_move_string()
+            );
+            board.make_move(decided);
+
+            if board.result().is_over() || !board.is_legal(column) {
+                break;
+            }
+            board.make_move(column);
+            if board.result().is_over() {
+                break;
+            }
+        }
+        assert!(board.ply() > 2);
+    }
+
+    #[test]
+    fn search_statistics_are_kept_until_the_next_move() {
+        let board = Board::from_move_string("4453").unwrap();
+        let mut book = OpeningBook::new();
+        book.insert(&Board::new(), 3);
+        let mut ai = AIPlayer::with_book(Difficulty::Easy, book);
+        assert_eq!(*ai.last_stats(), SearchStats::default());
+
+        ai.decide_move(&board, Token::Player1);
+        let stats = *ai.last_stats();
+        assert!(stats.nodes > 0);
+        // The moves of the board can be played in other orders, so positions are seen again.
+        assert!(stats.tt_hits > 0);
+        assert!(stats.tt_stores > 0);
+        assert_eq!(stats.depth, 3);
+        assert!(stats.max_ply >= 3);
+
+        // A move from the book needs no search.
+        assert_eq!(ai.decide_move(&Board::new(), Token::Player1), 3);
+        assert_eq!(*ai.last_stats(), SearchStats::default());
+    }
+
+    #[test]
+    fn numeric_depth_searches_like_the_difficulty() {
+        assert_eq!(usize::from(Difficulty::Master), 9);
+
+        let board = Board::from_move_string("4453").unwrap();
+        let mut depth = AIPlayer::with_depth(9).with_threads(1);
+        let mut difficulty = AIPlayer::new(Difficulty::Master).with_threads(1);
+
+        assert_eq!(
+            depth.evaluate_moves(&board, Token::Player1),
+            difficulty.evaluate_moves(&board, Token::Player1)
+        );
+        assert_eq!(depth.last_stats().nodes, difficulty.last_stats().nodes);
+        assert_eq!(depth.last_stats().depth, 9);
+    }
+
+    #[test]
+    fn analysis_scores_every_move() {
+        let board = Board::from_move_string("4453").unwrap();
+        let mut ai = AIPlayer::with_seed(Difficulty::Easy, 3);
+
+        let analysis = ai.analyze(&board, Token::Player1);
+        let mut columns: Vec<Column> = analysis.moves.iter().map(|&(column, _)| column).collect();
+        columns.sort_unstable();
+        assert_eq!(columns, board.legal_moves().collect::<Vec<_>>());
+        assert!(analysis.moves.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+        assert_eq!(analysis.moves[0].1, analysis.score);
+
+        let best: Vec<Column> = analysis
+            .moves
+            .iter()
+            .filter(|&&(_, value)| value == analysis.score)
+            .map(|&(column, _)| column)
+            .collect();
+        assert!(best.contains(&analysis.best));
+        for _ in 0..10 {
+            assert!(best.contains(&ai.decide_move(&board, Token::Player1)));
+        }
+    }
+
+    #[test]
+    fn principal_variation_reaches_the_depth_or_the_end_of_the_game() {
+        for moves in &["", "4453", "4455", "141576", "3344"] {
+            let board = Board::from_move_string(moves).unwrap();
+            let mut ai = AIPlayer::with_depth(5);
+
+            // The search plays the root move and then searches to its depth beyond it.
+            let pv = ai.principal_variation(&board);
+            let mut line = board;
+            for &column in &pv {
+                assert!(!line.result().is_over(), "{} then {:?}", moves, pv);
+                assert!(line.is_legal(column), "{} then {:?}", moves, pv);
+                line.make_move(column);
+            }
+            assert!(
+                line.result().is_over() || pv.len() == 6,
+                "{} then {:?}",
+                moves,
+                pv
+            );
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn search_on_a_rayon_pool_gives_the_values_of_a_single_thread() {
+        let board = Board::from_move_string("4453").unwrap();
+        let token = board.current_player();
+        let expected = AIPlayer::with_depth(6)
+            .with_threads(1)
+            .evaluate_moves(&board, token);
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(3)
+            .build()
+            .unwrap();
+        let mut ai = pool.install(|| AIPlayer::with_depth(6));
+        assert_eq!(ai.threads, 3);
+        assert_eq!(pool.install(|| ai.evaluate_moves(&board, token)), expected);
+    }
+
+    #[test]
+    fn quiescence_finds_wins_beyond_the_depth() {
+        let board = Board::from_move_string("4455").unwrap();
+        let mut ai = AIPlayer::with_depth(1);
+
+        // Either move makes two threats on the bottom row, so the game is won two moves later, past
+        // the depth of the search.
+        let moves = ai.evaluate_moves(&board, Token::Player1);
+        let mut wins: Vec<Column> = moves
+            .iter()
+            .filter(|&&(_, value)| value > eval::WIN - MAX_DEPTH as Score)
+            .map(|&(column, _)| column)
+            .collect();
+        wins.sort_unstable();
+        assert_eq!(wins, [2, 5]);
+
+        // The opponent sees the loss coming as well.
+        let board = Board::from_move_string("44553").unwrap();
+        let moves = ai.evaluate_moves(&board, Token::Player2);
+        assert!(moves[0].1 < -(eval::WIN - MAX_DEPTH as Score));
+    }
+
+    #[test]
+    fn cancelled_search_plays_a_legal_move() {
+        let board = Board::from_move_string("4453").unwrap();
+        let cancel = AtomicBool::new(true);
+
+        // Searching to this depth would take far too long if the search was not cancelled.
+        let mut ai = AIPlayer::with_depth(30).with_threads(1);
+        let options = SearchOptions {
+            cancel: Some(&cancel),
+            progress: None,
+        };
+        let column = ai.decide_move_with(&board, board.current_player(), options);
+
+        assert!(board.is_legal(column));
+        assert_eq!(ai.last_stats().depth, 1);
+    }
+
+    #[test]
+    fn progress_is_reported_for_each_depth() {
+        let board = Board::new();
+        let mut depths = Vec::new();
+
+        let mut ai = AIPlayer::with_depth(4).with_threads(1);
+        let mut progress = |stats: &SearchStats| depths.push((stats.depth, stats.nodes));
+        let options = SearchOptions {
+            cancel: None,
+            progress: Some(&mut progress),
+        };
+        ai.decide_move_with(&board, Token::Player1, options);
+
+        assert_eq!(
+            depths.iter().map(|&(depth, _)| depth).collect::<Vec<_>>(),
+            [1, 2, 3, 4]
+        );
+        assert!(depths.windows(2).all(|pair| pair[0].1 < pair[1].1));
+    }
+
+    #[test]
+    fn aspiration_windows_give_the_values_of_a_full_window() {
+        let no_aspiration = Enhancements {
+            aspiration: false,
+            ..Enhancements::default()
+        };
+
+        let (mut fail_high, mut fail_low) = (false, false);
+        for moves in ["", "4453", "3344", "444455", "7654", "2255"] {
+            let board = Board::from_move_string(moves).unwrap();
+            let (values, _) = search_with(&board, 7, Enhancements::default());
+            let (expected, _) = search_with(&board, 7, no_aspiration);
+            assert_eq!(values, expected, "moves {:?}", moves);
+
+            // A value outside the window around the previous value was searched again.
+            for pair in values.windows(2) {
+                for (&previous, &value) in pair[0].iter().zip(&pair[1]) {
+                    fail_high |= value >= previous + ASPIRATION_DELTA;
+                    fail_low |= value <= previous - ASPIRATION_DELTA;
                 }
             }
         }
+        assert!(fail_high && fail_low);
     }
 
-    total_score
-}
+    #[test]
+    fn decide_move_stats_gives_the_statistics_of_the_search() {
+        let board = Board::from_move_string("4453").unwrap();
+
+        // The same search as the player makes, on one thread with a table of the same size.
+        let mut ai = AIPlayer::with_depth(5)
+            .with_threads(1)
+            .with_table_memory(1 << 20);
+        let (column, stats) = ai.decide_move_stats(&board, Token::Player1);
+        assert!(board.is_legal(column));
+        assert_eq!(stats, *ai.last_stats());
+        assert_eq!(stats.nodes, nodes_with(&board, 5, Enhancements::default()));
+
+        // Deeper searches reach more positions by more than one order of moves.
+        let mut ai = AIPlayer::with_depth(7);
+        let (_, deeper) = ai.decide_move_stats(&board, Token::Player1);
+        assert!(deeper.nodes > stats.nodes);
+        assert!(deeper.tt_hits > stats.tt_hits);
+        assert!(deeper.tt_stores > stats.tt_stores);
+    }
+
+    #[test]
+    fn mate_in_counts_the_plies_to_the_win() {
+        let mut ai = AIPlayer::new(Difficulty::Easy);
+        let mate_in =
+            |ai: &mut AIPlayer, moves| ai.mate_in(&Board::from_move_string(moves).unwrap());
+
+        // The first player makes three in a row that is open at both ends, and wins next turn.
+        assert_eq!(mate_in(&mut ai, "4455"), Some(3));
+        assert_eq!(
+            mate_in(&mut ai, "2252576253462244111563365343671351441"),
+            Some(-4)
+        );
+        assert_eq!(
+            mate_in(&mut ai, "23163416124767223154467471272416755633"),
+            None
+        );
+        assert_eq!(mate_in(&mut ai, "1212121"), Some(0));
+    }
+
+    #[test]
+    fn cleared_table_searches_like_a_new_one() {
+        let board = Board::from_move_string("4453").unwrap();
+        let mut ai = AIPlayer::with_depth(5).with_threads(1);
+
+        ai.decide_move(&board, Token::Player1);
+        let fresh = ai.last_stats().nodes;
+        ai.decide_move(&board, Token::Player1);
+        assert!(ai.last_stats().nodes < fresh);
 
-fn get_length(
-    board: &Board,
-    pos: (Column, Column),
-    direction: (ColumnDiff, ColumnDiff),
-    side: Token,
-) -> (Column, Column) {
-    let mut current = 0;
-    let mut possible = 0;
+        ai.clear_table();
+        ai.decide_move(&board, Token::Player1);
+        assert_eq!(ai.last_stats().nodes, fresh);
 
-    let mut row = pos.0 as i8;
-    let mut column = pos.1 as i8;
+        // Starting a new game clears the table as well.
+        ai.decide_move(&board, Token::Player1);
+        ai.new_game();
+        ai.decide_move(&board, Token::Player1);
+        assert_eq!(ai.last_stats().nodes, fresh);
+    }
+
+    #[test]
+    fn table_is_emptied_when_its_generation_wraps_around() {
+        let mut ttable = TranspositionTable::new(16);
+        ttable.store(entry(1, 5));
+
+        for _ in 0..=u8::MAX {
+            ttable.clear();
+        }
+        assert!(ttable.probe(1).is_none());
+    }
+
+    #[test]
+    fn difficulty_names_round_trip() {
+        for &difficulty in Difficulty::ALL {
+            let name = difficulty.to_string();
+            assert_eq!(name.parse(), Ok(difficulty));
+            assert_eq!(name.to_lowercase().parse(), Ok(difficulty));
+            assert_eq!(name.to_uppercase().parse(), Ok(difficulty));
+        }
+        assert_eq!(Difficulty::Master.to_string(), "Master");
+
+        let err = "impossible".parse::<Difficulty>().unwrap_err();
+        assert_eq!(err.name, "impossible");
+        assert_eq!(
+            err.to_string(),
+            "unknown difficulty \"impossible\", expected one of Easy, Medium, Hard, Master, Unfair"
+        );
+    }
+
+    #[test]
+    fn depth_can_go_beyond_the_difficulties() {
+        let board = Board::from_move_string("4455141517").unwrap();
+        let mut depth = AIPlayer::with_depth(9);
+        let mut difficulty = AIPlayer::new(Difficulty::Master);
+        assert_eq!(
+            depth.decide_move(&board, Token::Player1),
+            difficulty.decide_move(&board, Token::Player1)
+        );
+
+        // Searching to the end of the game finds the win the solver sees.
+        let board = Board::from_move_string("3723651443715621542266323342").unwrap();
+        let mut ai = AIPlayer::with_depth(13);
+        let analysis = ai.analyze(&board, board.current_player());
+        assert_eq!(ai.last_stats().depth, 13);
+        assert!(analysis.score > eval::WIN);
+    }
+
+    #[test]
+    #[should_panic(expected = "depth must be at least 1")]
+    fn zero_depth_panics() {
+        AIPlayer::with_depth(0);
+    }
+
+    #[test]
+    fn blunders_are_made_at_the_blunder_rate() {
+        // The center is the only best move at this depth.
+        let board = Board::new();
+        let blunders = |rate: f64, seed: u64| -> Vec<Column> {
+            let mut ai = AIPlayer::with_seed(Difficulty::Easy, seed).with_blunder_rate(rate);
+            (0..100)
+                .map(|_| ai.decide_move(&board, Token::Player1))
+                .filter(|&column| column != 3)
+                .collect()
+        };
+
+        assert!(blunders(0.0, 1).is_empty());
+        assert_eq!(blunders(1.0, 1).len(), 100);
+        let mut columns = blunders(1.0, 1);
+        columns.sort_unstable();
+        columns.dedup();
+        assert_eq!(columns, [0, 1, 2, 4, 5, 6]);
+
+        let count = blunders(0.3, 1).len();
+        assert!((15..=45).contains(&count), "{} blunders", count);
+        assert_eq!(blunders(0.3, 1), blunders(0.3, 1));
+    }
 
-    const ROWS: Range<i8> = 0..(HEIGHT as i8);
-    const COLUMNS: Range<i8> = 0..(WIDTH as i8);
+    #[test]
+    #[should_panic(expected = "blunder rate must be between 0 and 1")]
+    fn blunder_rate_above_1_panics() {
+        let _ = AIPlayer::new(Difficulty::Easy).with_blunder_rate(1.5);
+    }
 
-    loop {
-        row += direction.0;
-        column += direction.1;
+    #[test]
+    fn moves_are_evaluated_for_the_player_making_them() {
+        // The second player makes two threats on the bottom row with either move.
+        let board = Board::from_move_string("14455").unwrap();
+        let mut ai = AIPlayer::with_depth(3);
 
-        // Check the cell is inbounds, this is optimised in release builds.
-        if !(ROWS.contains(&row) && COLUMNS.contains(&column)) {
-            break;
+        let moves = ai.evaluate_moves(&board, Token::Player2);
+        assert_eq!(moves.len(), WIDTH as usize);
+        assert!(moves[0].1 > eval::WIN);
+        for _ in 0..10 {
+            let column = ai.decide_move(&board, Token::Player2);
+            let value = moves.iter().find(|&&(c, _)| c == column).unwrap().1;
+            assert_eq!(value, moves[0].1);
         }
 
-        match board.token_at(row as Column, column as Column) {
-            Some(token) if token == side => current += 1,
-            Some(_) => break,
-            _ => {}
+        // The first player cannot stop both threats.
+        let board = Board::from_move_string("144553").unwrap();
+        let moves = ai.evaluate_moves(&board, Token::Player1);
+        assert!(moves
+            .iter()
+            .all(|&(_, value)| value < -eval::WIN + MAX_DEPTH as Score));
+    }
+
+    #[test]
+    fn blunders_never_hand_the_opponent_a_win() {
+        // Playing in the center lets the second player complete the second row.
+        let board = Board::from_move_string("56751627").unwrap();
+        let mut ai = AIPlayer::with_seed(Difficulty::Easy, 5).with_blunder_rate(1.0);
+        for _ in 0..50 {
+            let column = ai.decide_move(&board, Token::Player1);
+            assert!(!hands_win(&board, column), "{}", column);
         }
 
-        possible += 1;
+        // A forced win is never thrown away.
+        let board = Board::from_move_string("4455141517").unwrap();
+        for _ in 0..10 {
+            let column = ai.decide_move(&board, Token::Player1);
+            let mut next = board;
+            next.make_move(column);
+            assert_eq!(next.winner(), Some(Token::Player1));
+        }
+    }
+
+    #[test]
+    fn ties_between_negative_values_are_kept() {
+        let root_moves = |values: &[Score]| -> Vec<RootMove> {
+            (0..)
+                .zip(values)
+                .map(|(column, &value)| RootMove {
+                    column,
+                    value,
+                    pv: vec![column],
+                })
+                .collect()
+        };
+        let columns = |root_moves: Vec<RootMove>| -> Vec<Column> {
+            root_moves
+                .iter()
+                .map(|root_move| root_move.column)
+                .collect()
+        };
+
+        let moves = best_moves(root_moves(&[-50, -10, -10, -30]));
+        assert_eq!(columns(moves), [1, 2]);
+        let moves = best_moves(root_moves(&[Score::MIN, -1, Score::MIN, -1]));
+        assert_eq!(columns(moves), [1, 3]);
+        let moves = best_moves(root_moves(&[Score::MIN, Score::MIN]));
+        assert_eq!(columns(moves), [0, 1]);
     }
 
-    (current, possible)
+    #[test]
+    fn finished_games_have_no_move() {
+        let mut ai = AIPlayer::with_depth(3);
+        let won = Board::from_move_string("1212121").unwrap();
+        let drawn = Board::from_move_string("112224243125463563541273141564336756677757").unwrap();
+
+        assert_eq!(ai.try_decide_move(&won, Token::Player2), Err(NoLegalMove));
+        assert_eq!(ai.try_decide_move(&drawn, Token::Player1), Err(NoLegalMove));
+
+        let board = Board::from_move_string("4453").unwrap();
+        let column = ai.try_decide_move(&board, Token::Player1).unwrap();
+        assert!(board.is_legal(column));
+    }
 }
@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::board::{Board, Column, MoveStringError};
+
+/// An opening book, recommending the move to play on known boards.
+///
+/// Boards are looked up by their canonical key, so a move recommended for a board is also
+/// recommended, mirrored, for the mirror of the board.
+///
+/// A book can be parsed from text with a line for each board, of the moves played to reach the
+/// board and the recommended column separated by `->`, in the format of
+/// [`Board::from_move_string`]. Empty lines and lines starting with `#` are ignored.
+///
+/// ```text
+/// # Always start in the center.
+///  -> 4
+/// 4 -> 4
+/// 44 -> 4
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct OpeningBook {
+    /// The recommended moves, keyed by canonical key, for the canonical board.
+    moves: HashMap<u64, Column>,
+}
+
+impl OpeningBook {
+    /// Creates an empty book.
+    pub fn new() -> OpeningBook {
+        OpeningBook::default()
+    }
+
+    /// Loads a book from a text file.
+    pub fn load(path: impl AsRef<Path>) -> Result<OpeningBook, BookError> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// Gets the number of boards in the book.
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+
+    /// Gets whether the book is empty.
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+    }
+
+    /// Recommends a move on the board, replacing any move already recommended for it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the move is illegal.
+    pub fn insert(&mut self, board: &Board, column: Column) {
+        assert!(board.is_legal(column), "illegal move: {}", column);
+
+        let (key, mirrored) = board.canonical_key();
+        self.moves
+            .insert(key, Board::reflect_column(column, mirrored));
+    }
+
+    /// Gets the move recommended on the board, if it is in the book.
+    pub fn get(&self, board: &Board) -> Option<Column> {
+        let (key, mirrored) = board.canonical_key();
+        self.moves
+            .get(&key)
+            .map(|&column| Board::reflect_column(column, mirrored))
+    }
+}
+
+impl FromStr for OpeningBook {
+    type Err = BookError;
+
+    fn from_str(s: &str) -> Result<OpeningBook, BookError> {
+        let mut book = OpeningBook::new();
+
+        for (index, line) in s.lines().enumerate() {
+            let line_number = index + 1;
+
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (moves, column) = match line.split_once("->") {
+                Some((moves, column)) => (moves.trim(), column.trim()),
+                None => return Err(BookError::InvalidLine { line: line_number }),
+            };
+
+            let board =
+                Board::from_move_string(moves).map_err(|error| BookError::InvalidMoves {
+                    line: line_number,
+                    error,
+                })?;
+            let column = match Column::from_str(column) {
+                Ok(column) if column > 0 && board.is_legal(column - 1) => column - 1,
+                _ => return Err(BookError::IllegalColumn { line: line_number }),
+            };
+
+            book.insert(&board, column);
+        }
+
+        Ok(book)
+    }
+}
+
+/// An error from loading an opening book.
+#[derive(Debug)]
+pub enum BookError {
+    /// An error reading the file of the book.
+    Io(io::Error),
+    /// A line that does not separate the moves and the column with `->`.
+    InvalidLine { line: usize },
+    /// A line with moves that could not be played.
+    InvalidMoves { line: usize, error: MoveStringError },
+    /// A line with a column that is not a legal move on its board.
+    IllegalColumn { line: usize },
+}
+
+impl fmt::Display for BookError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BookError::Io(err) => err.fmt(f),
+            BookError::InvalidLine { line } => {
+                write!(f, "line {}: expected `moves -> column`", line)
+            }
+            BookError::InvalidMoves { line, error } => write!(f, "line {}: {}", line, error),
+            BookError::IllegalColumn { line } => write!(f, "line {}: illegal column", line),
+        }
+    }
+}
+
+impl Error for BookError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BookError::Io(err) => Some(err),
+            BookError::InvalidMoves { error, .. } => Some(error),
+            BookError::InvalidLine { .. } | BookError::IllegalColumn { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for BookError {
+    fn from(err: io::Error) -> BookError {
+        BookError::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::ai::{AIPlayer, Difficulty, SearchStats};
+    use crate::player::Token;
+
+    #[test]
+    fn book_move_is_played_without_searching() {
+        let book: OpeningBook = "# Start on the edge.\n -> 1\n".parse().unwrap();
+        let mut ai = AIPlayer::with_book(Difficulty::Unfair, book);
+
+        assert_eq!(ai.decide_move(&Board::new(), Token::Player1), 0);
+        assert_eq!(*ai.last_stats(), SearchStats::default());
+    }
+
+    #[test]
+    fn book_moves_are_mirrored() {
+        let book: OpeningBook = "1 -> 2\n".parse().unwrap();
+
+        assert_eq!(book.len(), 1);
+        assert_eq!(book.get(&Board::from_move_string("1").unwrap()), Some(1));
+        assert_eq!(book.get(&Board::from_move_string("7").unwrap()), Some(5));
+        assert_eq!(book.get(&Board::from_move_string("2").unwrap()), None);
+    }
+
+    #[test]
+    fn invalid_lines_are_errors() {
+        assert!(matches!(
+            "4 4".parse::<OpeningBook>(),
+            Err(BookError::InvalidLine { line: 1 })
+        ));
+        assert!(matches!(
+            "\n48 -> 4".parse::<OpeningBook>(),
+            Err(BookError::InvalidMoves { line: 2, .. })
+        ));
+        assert!(matches!(
+            "111111 -> 1".parse::<OpeningBook>(),
+            Err(BookError::IllegalColumn { line: 1 })
+        ));
+    }
+}
@@ -0,0 +1,553 @@
+use crate::board::{BitBoard, Board, Column, CONNECT, HEIGHT, WIDTH};
+use crate::player::ai::Score;
+use crate::player::Token;
+
+/// The default value of a won game, before adjusting for how soon it is won.
+pub const WIN: Score = 10_000;
+
+/// The cells of the center column.
+const CENTER: BitBoard = ((1 << HEIGHT) - 1) << (WIDTH / 2 * (HEIGHT + 1));
+
+/// A heuristic evaluation of boards, used by the search once it reaches its depth.
+pub trait Evaluator: Send + Sync + 'static {
+    /// Evaluates a board on which the game is not finished, from the perspective of `side`.
+    ///
+    /// Higher values are better for `side`. Values must be far smaller in magnitude than
+    /// [`Evaluator::win_value`], so they are never mistaken for a finished game. Mirrored boards
+    /// share entries in the transposition table, so a board must have the same value as its mirror.
+    fn evaluate(&self, board: &Board, side: Token) -> Score;
+
+    /// Gets the value of a won game, before adjusting for how soon it is won.
+    fn win_value(&self) -> Score {
+        WIN
+    }
+}
+
+/// The weights used by [`DefaultEvaluator`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct HeuristicWeights {
+    /// The value of a won game, before adjusting for how soon it is won.
+    pub win: Score,
+    /// The value of each token in a window that can still be completed.
+    pub per_token: Score,
+    /// The extra value of a window that can still be completed with two tokens in it.
+    pub two_in_a_row: Score,
+    /// The extra value of a window that can still be completed with three tokens in it.
+    pub three_in_a_row: Score,
+    /// The value of each empty cell that would complete a line.
+    pub threat: Score,
+    /// The extra value of a threat in a row of the parity that favours its player: odd rows for
+    /// the first player and even rows for the second, counting from 1 at the bottom.
+    pub parity_threat: Score,
+    /// The value of each token in the center column, which is part of more lines than any other.
+    pub center: Score,
+}
+
+impl Default for HeuristicWeights {
+    fn default() -> HeuristicWeights {
+        HeuristicWeights {
+            win: WIN,
+            per_token: 10,
+            two_in_a_row: 0,
+            three_in_a_row: 0,
+            threat: 10,
+            parity_threat: 30,
+            center: 20,
+        }
+    }
+}
+
+/// The default evaluator, which scores each window of four cells in a line that a player can
+/// still complete by how many of their tokens are already in it.
+///
+/// Each window is scored once, however many tokens it holds. Cells that would complete a line are
+/// scored as threats, more so when the parity of their row favours the player, since the player
+/// with the right threats usually wins once the board fills up. Tokens in the center column score
+/// extra, so the center is taken early, before any lines form. The windows, threats and center
+/// tokens of the opponent count against the side being evaluated.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultEvaluator {
+    weights: HeuristicWeights,
+}
+
+impl DefaultEvaluator {
+    /// Creates an evaluator with the given weights.
+    pub fn new(weights: HeuristicWeights) -> DefaultEvaluator {
+        DefaultEvaluator { weights }
+    }
+
+    /// Gets the value of a window with `len` tokens in it.
+    fn window_value(&self, len: Column) -> Score {
+        let bonus = match len {
+            2 => self.weights.two_in_a_row,
+            3 => self.weights.three_in_a_row,
+            _ => 0,
+        };
+
+        self.weights.per_token * Score::from(len) + bonus
+    }
+
+    /// Gets the value of the threats of the given token.
+    fn threats_value(&self, board: &Board, token: Token) -> Score {
+        let (odd, even) = board.threat_parity(token);
+        let good = match token {
+            Token::Player1 => odd,
+            Token::Player2 => even,
+        };
+
+        (odd + even) as Score * self.weights.threat + good as Score * self.weights.parity_threat
+    }
+
+    /// Gets the value of the tokens of the given token in the center column.
+    fn center_value(&self, board: &Board, token: Token) -> Score {
+        (board.player_board(token) & CENTER).count_ones() as Score * self.weights.center
+    }
+}
+
+impl Evaluator for DefaultEvaluator {
+    fn win_value(&self) -> Score {
+        self.weights.win
+    }
+
+    fn evaluate(&self, board: &Board, side: Token) -> Score {
+        let own = board.open_windows(side);
+        let opponent = board.open_windows(side.opponent());
+
+        let windows: Score = (1..CONNECT)
+            .map(|len| {
+                let windows = own[len as usize] as Score - opponent[len as usize] as Score;
+                windows * self.window_value(len)
+            })
+            .sum();
+
+        windows + self.threats_value(board, side) - self.threats_value(board, side.opponent())
+            + self.center_value(board, side)
+            - self.center_value(board, side.opponent())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::board::BOARD_SIZE;
+    use crate::player::ai::AIPlayer;
+    use crate::player::random::RandomPlayer;
+
+    const POSITIONS: &[&str] = &["", "1", "4", "1122", "44455", "3344556", "2252576253462244"];
+
+    /// Counts the open windows of the given token by checking each window cell by cell.
+    fn scan_windows(board: &Board, token: Token) -> [u32; CONNECT as usize] {
+        let mut counts = [0; CONNECT as usize];
+
+        for &(dx, dy) in &[(1, 0), (0, 1), (1, 1), (1, -1)] {
+            for column in 0..WIDTH as i32 {
+                for row in 0..HEIGHT as i32 {
+                    let cells: Vec<(i32, i32)> = (0..CONNECT as i32)
+                        .map(|i| (row + i * dy, column + i * dx))
+                        .collect();
+                    let fits = cells.iter().all(|&(row, column)| {
+                        (0..HEIGHT as i32).contains(&row) && (0..WIDTH as i32).contains(&column)
+                    });
+                    if !fits {
+                        continue;
+                    }
+
+                    let tokens: Vec<Option<Token>> = cells
+                        .iter()
+                        .map(|&(row, column)| board.token_at(row as Column, column as Column))
+                        .collect();
+                    if tokens.contains(&Some(token.opponent())) {
+                        continue;
+                    }
+
+                    let len = tokens.iter().filter(|&&cell| cell == Some(token)).count();
+                    if len < CONNECT as usize {
+                        counts[len] += 1;
+                    }
+                }
+            }
+        }
+
+        counts
+    }
+
+    /// Gets every position of some random games.
+    fn random_positions() -> Vec<Board> {
+        let mut boards = Vec::new();
+        for seed in 0..20 {
+            let mut player = RandomPlayer::with_seed(seed);
+            let mut board = Board::new();
+
+            while !board.result().is_over() {
+                boards.push(board);
+                board.make_move(player.decide_move(&board));
+            }
+        }
+
+        boards
+    }
+
+    #[test]
+    fn each_window_is_scored_once() {
+        let weights = HeuristicWeights {
+            per_token: 1,
+            threat: 0,
+            parity_threat: 0,
+            center: 0,
+            ..HeuristicWeights::default()
+        };
+        let evaluator = DefaultEvaluator::new(weights);
+
+        // A token in the corner is in one window across, one up and one diagonal.
+        let board = Board::from_move_string("1").unwrap();
+        assert_eq!(evaluator.evaluate(&board, Token::Player1), 3);
+
+        for moves in POSITIONS {
+            let board = Board::from_move_string(moves).unwrap();
+            let tokens = |token| -> Score {
+                let counts = scan_windows(&board, token);
+                (0..CONNECT as usize)
+                    .map(|len| (len * counts[len] as usize) as Score)
+                    .sum()
+            };
+
+            assert_eq!(
+                evaluator.evaluate(&board, Token::Player1),
+                tokens(Token::Player1) - tokens(Token::Player2),
+                "{}",
+                moves
+            );
+        }
+    }
+
+    /// An evaluator that only values tokens in the first and last columns.
+    struct Edges;
+
+    impl Evaluator for Edges {
+        fn evaluate(&self, board: &Board, side: Token) -> Score {
+            let column: BitBoard = (1 << HEIGHT) - 1;
+            let edges = column | column << ((WIDTH - 1) * (HEIGHT + 1));
+            (board.player_board(side) & edges).count_ones() as Score
+                - (board.player_board(side.opponent()) & edges).count_ones() as Score
+        }
+
+        fn win_value(&self) -> Score {
+            1000
+        }
+    }
+
+    #[test]
+    fn custom_evaluator_decides_the_move() {
+        let edges = [0, WIDTH - 1];
+        let mut ai = AIPlayer::with_depth(1).with_evaluator(Edges);
+        assert!(edges.contains(&ai.decide_move(&Board::new(), Token::Player1)));
+
+        let board = Board::from_move_string("4").unwrap();
+        assert!(edges.contains(&ai.decide_move(&board, Token::Player2)));
+    }
+
+    #[test]
+    fn custom_evaluator_gives_the_value_of_a_win() {
+        let board = Board::from_move_string("121212").unwrap();
+        let mut ai = AIPlayer::with_depth(3).with_evaluator(Edges);
+
+        let (column, score) = ai.evaluate_moves(&board, Token::Player1)[0];
+        assert_eq!(column, 0);
+        // The value is adjusted by at most the number of cells for how soon the win is.
+        assert!((1000..1000 + Score::from(BOARD_SIZE)).contains(&score));
+    }
+
+    #[test]
+    fn values_are_linear_in_the_weights() {
+        let weights = HeuristicWeights {
+            two_in_a_row: 5,
+            three_in_a_row: 20,
+            center: 3,
+            ..HeuristicWeights::default()
+        };
+        let doubled = HeuristicWeights {
+            win: weights.win,
+            per_token: weights.per_token * 2,
+            two_in_a_row: weights.two_in_a_row * 2,
+            three_in_a_row: weights.three_in_a_row * 2,
+            threat: weights.threat * 2,
+            parity_threat: weights.parity_threat * 2,
+            center: weights.center * 2,
+        };
+        let zero = HeuristicWeights {
+            win: weights.win,
+            per_token: 0,
+            two_in_a_row: 0,
+            three_in_a_row: 0,
+            threat: 0,
+            parity_threat: 0,
+            center: 0,
+        };
+
+        for moves in POSITIONS {
+            let board = Board::from_move_string(moves).unwrap();
+            let value = |weights| DefaultEvaluator::new(weights).evaluate(&board, Token::Player1);
+
+            assert_eq!(value(doubled), 2 * value(weights), "{}", moves);
+            assert_eq!(value(zero), 0, "{}", moves);
+            assert_eq!(
+                value(HeuristicWeights::default()),
+                DefaultEvaluator::default().evaluate(&board, Token::Player1),
+                "{}",
+                moves
+            );
+        }
+    }
+
+    #[test]
+    fn win_weight_is_the_value_of_a_win() {
+        let board = Board::from_move_string("121212").unwrap();
+        let weights = HeuristicWeights {
+            win: 500,
+            ..HeuristicWeights::default()
+        };
+        let mut ai = AIPlayer::with_depth(3).with_weights(weights);
+
+        let (column, score) = ai.evaluate_moves(&board, Token::Player1)[0];
+        assert_eq!(column, 0);
+        assert!((500..500 + Score::from(BOARD_SIZE)).contains(&score));
+    }
+
+    #[test]
+    fn open_windows_match_a_scan_of_each_window() {
+        for moves in POSITIONS {
+            let board = Board::from_move_string(moves).unwrap();
+            for &token in &[Token::Player1, Token::Player2] {
+                assert_eq!(
+                    board.open_windows(token),
+                    scan_windows(&board, token),
+                    "{}",
+                    moves
+                );
+            }
+        }
+
+        // Check every position of some random games as well.
+        for board in random_positions() {
+            for &token in &[Token::Player1, Token::Player2] {
+                assert_eq!(
+                    board.open_windows(token),
+                    scan_windows(&board, token),
+                    "{}",
+                    board.to_move_string()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn open_windows_are_counted_by_tokens() {
+        // There are 24 windows across, 21 up and 12 along each diagonal.
+        let board = Board::new();
+        assert_eq!(board.open_windows(Token::Player1), [69, 0, 0, 0]);
+
+        // A token in the middle of the bottom row is in 4 windows across, 1 up and 1 along each
+        // diagonal, and takes them away from the opponent.
+        let board = Board::from_move_string("4").unwrap();
+        assert_eq!(board.open_windows(Token::Player1), [62, 7, 0, 0]);
+        assert_eq!(board.open_windows(Token::Player2), [62, 0, 0, 0]);
+
+        // Three tokens across with both ends open are in two windows, and two of them are in a
+        // third window on the left, since the one on the right is blocked.
+        let board = Board::from_move_string("374757").unwrap();
+        assert_eq!(board.open_windows(Token::Player1)[3], 2);
+        assert_eq!(board.open_windows(Token::Player1)[2], 1);
+    }
+
+    /// Compares the time taken to evaluate boards with bitboards and by scanning each window.
+    ///
+    /// Run with `cargo test --release -- --ignored --nocapture` to see the times.
+    #[test]
+    #[ignore]
+    fn bitboard_evaluation_is_faster_than_scanning() {
+        let boards = random_positions();
+
+        let evaluator = DefaultEvaluator::default();
+        let start = Instant::now();
+        let evaluated: Score = boards
+            .iter()
+            .map(|board| evaluator.evaluate(board, Token::Player1))
+            .sum();
+        let bitboard = start.elapsed();
+
+        let start = Instant::now();
+        let scanned: u32 = boards
+            .iter()
+            .map(|board| {
+                let own = scan_windows(board, Token::Player1);
+                let opponent = scan_windows(board, Token::Player2);
+                own.iter().chain(&opponent).sum::<u32>()
+            })
+            .sum();
+        let scanning = start.elapsed();
+
+        // Use the results so neither loop is optimised away.
+        println!(
+            "{} boards: bitboard {:?} ({}), scanning {:?} ({})",
+            boards.len(),
+            bitboard,
+            evaluated,
+            scanning,
+            scanned
+        );
+    }
+
+    #[test]
+    fn open_three_is_better_than_isolated_tokens() {
+        let evaluator = DefaultEvaluator::default();
+
+        // The same tokens, in a row with both ends open or apart from each other.
+        let three = Board::from_move_string("374757").unwrap();
+        let isolated = Board::from_move_string("173757").unwrap();
+
+        assert!(
+            evaluator.evaluate(&three, Token::Player1)
+                > evaluator.evaluate(&isolated, Token::Player1)
+        );
+        assert!(
+            evaluator.evaluate(&three, Token::Player2)
+                < evaluator.evaluate(&isolated, Token::Player2)
+        );
+    }
+
+    #[test]
+    fn bitboard_evaluation_matches_scanning() {
+        let weights = HeuristicWeights {
+            per_token: 1,
+            threat: 0,
+            parity_threat: 0,
+            center: 0,
+            ..HeuristicWeights::default()
+        };
+        let evaluator = DefaultEvaluator::new(weights);
+
+        for board in random_positions() {
+            let tokens = |token| -> Score {
+                let counts = scan_windows(&board, token);
+                (0..CONNECT as usize)
+                    .map(|len| (len * counts[len] as usize) as Score)
+                    .sum()
+            };
+
+            assert_eq!(
+                evaluator.evaluate(&board, Token::Player1),
+                tokens(Token::Player1) - tokens(Token::Player2),
+                "{}",
+                board.to_move_string()
+            );
+        }
+    }
+
+    #[test]
+    fn default_weights_give_the_value_of_a_known_position() {
+        // The value of this position only changes along with the default weights.
+        let board = Board::from_move_string("2252576253462244").unwrap();
+        let evaluator = DefaultEvaluator::default();
+        assert_eq!(evaluator.evaluate(&board, Token::Player1), -20);
+        assert_eq!(evaluator.evaluate(&board, Token::Player2), 20);
+    }
+
+    #[test]
+    fn three_in_a_row_weight_changes_the_move() {
+        let board = Board::from_move_string("4535").unwrap();
+        let weights = HeuristicWeights {
+            three_in_a_row: 100,
+            ..HeuristicWeights::default()
+        };
+
+        let mut ai = AIPlayer::with_depth(2);
+        let mut three_in_a_row = AIPlayer::with_depth(2).with_weights(weights);
+        // Each weighting has a single best move here, so neither move is picked at random.
+        assert_ne!(
+            ai.decide_move(&board, Token::Player1),
+            three_in_a_row.decide_move(&board, Token::Player1)
+        );
+    }
+
+    #[test]
+    fn parity_threats_favour_odd_rows_for_the_first_player() {
+        let only = |threat, parity_threat| {
+            DefaultEvaluator::new(HeuristicWeights {
+                win: WIN,
+                per_token: 0,
+                two_in_a_row: 0,
+                three_in_a_row: 0,
+                threat,
+                parity_threat,
+                center: 0,
+            })
+        };
+        let threats = only(1, 0);
+        let parity_threats = only(0, 1);
+
+        // The first player threatens to complete the bottom row, in row 1.
+        let board = Board::from_move_string("11223").unwrap();
+        assert_eq!(threats.evaluate(&board, Token::Player1), 1);
+        assert_eq!(parity_threats.evaluate(&board, Token::Player1), 1);
+        assert_eq!(parity_threats.evaluate(&board, Token::Player2), -1);
+
+        // The first player threatens to complete a column, in row 4.
+        let board = Board::from_move_string("12121").unwrap();
+        assert_eq!(threats.evaluate(&board, Token::Player1), 1);
+        assert_eq!(parity_threats.evaluate(&board, Token::Player1), 0);
+
+        // Both players threaten to complete a column in row 4, which only favours the second.
+        let board = Board::from_move_string("121212").unwrap();
+        assert_eq!(threats.evaluate(&board, Token::Player1), 0);
+        assert_eq!(parity_threats.evaluate(&board, Token::Player1), -1);
+        assert_eq!(parity_threats.evaluate(&board, Token::Player2), 1);
+    }
+
+    #[test]
+    fn parity_threats_decide_a_shared_threat() {
+        // Both players threaten to complete a line in the third row of the empty fourth column,
+        // so whoever plays in its second row hands the cell to the other. The row is odd, so once
+        // the other columns fill up the second player is forced to, and the first player wins.
+        let board = Board::from_move_string("1655636333552115").unwrap();
+        assert!(AIPlayer::with_depth(1).solve(&board) > 0);
+
+        let weights = HeuristicWeights {
+            parity_threat: 0,
+            ..HeuristicWeights::default()
+        };
+        let evaluator = DefaultEvaluator::new(weights);
+        assert!(evaluator.evaluate(&board, Token::Player1) < 0);
+        assert!(DefaultEvaluator::default().evaluate(&board, Token::Player1) > 0);
+    }
+
+    #[test]
+    fn default_weights_prefer_the_center_at_depth_1() {
+        let mut ai = AIPlayer::with_depth(1);
+
+        let moves = ai.evaluate_moves(&Board::new(), Token::Player1);
+        assert_eq!(moves[0].0, WIDTH / 2);
+        assert!(moves[0].1 > moves[1].1);
+        assert_eq!(ai.decide_move(&Board::new(), Token::Player1), WIDTH / 2);
+    }
+
+    #[test]
+    fn center_weight_changes_the_move() {
+        let board = Board::from_move_string("12").unwrap();
+
+        let weights = HeuristicWeights {
+            center: 0,
+            ..HeuristicWeights::default()
+        };
+        let mut ai = AIPlayer::with_depth(3).with_weights(weights);
+        assert_ne!(ai.decide_move(&board, Token::Player1), WIDTH / 2);
+
+        let weights = HeuristicWeights {
+            center: 1000,
+            ..HeuristicWeights::default()
+        };
+        let mut ai = AIPlayer::with_depth(3).with_weights(weights);
+        assert_eq!(ai.decide_move(&board, Token::Player1), WIDTH / 2);
+    }
+}
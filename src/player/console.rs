@@ -1,49 +1,219 @@
-use std::process;
+use std::borrow::Cow;
+use std::io::{self, BufRead, Write};
 use std::str::FromStr;
 
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
 
 use crate::board::{Board, Column};
-use crate::player::{Player, Token};
+use crate::player::{Player, PlayerAction, Token};
 
 pub struct ConsolePlayer {
-    editor: Editor<()>,
+    input: Input,
 }
 
 impl ConsolePlayer {
     pub fn new() -> ConsolePlayer {
         ConsolePlayer {
-            editor: Editor::new(),
+            input: Input::Terminal(Editor::new()),
         }
     }
-}
 
-impl Player for ConsolePlayer {
-    fn decide_move(&mut self, board: &Board, token: Token) -> Column {
+    /// Creates a player that reads its moves from the lines of `reader` rather than the
+    /// terminal, such as a file or a string of moves.
+    ///
+    /// In a game, the player quits once the reader has run out of lines. Outside of a game,
+    /// [`ConsolePlayer::read_move`] returns [`ReadlineError::Eof`] at the end of the input.
+    pub fn from_reader(reader: impl BufRead + 'static) -> ConsolePlayer {
+        ConsolePlayer {
+            input: Input::Reader(Box::new(reader)),
+        }
+    }
+
+    /// Reads moves for `token` until a legal move is given.
+    ///
+    /// Returns [`ReadlineError::Eof`] at the end of the input, and [`ReadlineError::Interrupted`]
+    /// if the player pressed Ctrl-C.
+    pub fn read_move(&mut self, board: &Board, token: Token) -> Result<Column, ReadlineError> {
+        loop {
+            match self.read_action(board, token)? {
+                PlayerAction::Move(column) => return Ok(column),
+                _ => println!("\nOnly a move can be played now, try again"),
+            }
+        }
+    }
+
+    /// Reads actions for `token` until a legal move, `u` or `undo` to take back a move, `r` or
+    /// `resign` to give up the game, or `q` or `quit` to stop playing is given.
+    ///
+    /// Returns errors like [`ConsolePlayer::read_move`].
+    pub fn read_action(
+        &mut self,
+        board: &Board,
+        token: Token,
+    ) -> Result<PlayerAction, ReadlineError> {
         let prompt = format!("{} >> ", token);
 
         loop {
             println!("\n{}", board);
 
-            match self.editor.readline(&prompt) {
-                Ok(line) => {
-                    let line = line.trim();
+            let line = self.input.read_line(&prompt)?;
+            let line = line.trim();
 
-                    match Column::from_str(line).map(|column| column.saturating_sub(1)) {
-                        Ok(column) if board.is_legal(column) => return column,
-                        _ => println!("\nIllegal move '{}', try again", line),
-                    };
+            match line {
+                // An undo takes back a move of the player themselves, so they must have made one.
+                "u" | "undo" if board.ply() < 2 => {
+                    println!("\nThere is no move to take back, try again");
+                    continue;
                 }
-                Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => {
-                    println!("\nQuitting program");
-                    process::exit(1);
+                "u" | "undo" => return Ok(PlayerAction::Undo),
+                "r" | "resign" => return Ok(PlayerAction::Resign),
+                "q" | "quit" => return Ok(PlayerAction::Quit),
+                _ => {}
+            }
+
+            match Column::from_str(line) {
+                Ok(column) if column > 0 && board.is_legal(column - 1) => {
+                    return Ok(PlayerAction::Move(column - 1))
                 }
-                Err(err) => {
-                    println!("\nError: {:?}", err);
-                    process::exit(1);
+                _ => println!("\nIllegal move '{}', try again", line),
+            };
+        }
+    }
+}
+
+impl Default for ConsolePlayer {
+    fn default() -> ConsolePlayer {
+        ConsolePlayer::new()
+    }
+}
+
+impl Player for ConsolePlayer {
+    /// Quits the game at the end of the input, if the player pressed Ctrl-C, or if the input
+    /// could not be read.
+    fn decide_action(&mut self, board: &Board, token: Token) -> PlayerAction {
+        match self.read_action(board, token) {
+            Ok(action) => action,
+            Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => PlayerAction::Quit,
+            Err(err) => {
+                println!("\nError: {:?}", err);
+                PlayerAction::Quit
+            }
+        }
+    }
+
+    fn name(&self) -> Cow<'_, str> {
+        Cow::Borrowed("Console")
+    }
+}
+
+/// The source of the moves of a console player.
+enum Input {
+    Terminal(Editor<()>),
+    Reader(Box<dyn BufRead>),
+}
+
+impl Input {
+    /// Reads a line after writing the prompt, returning [`ReadlineError::Eof`] at the end of the
+    /// input.
+    fn read_line(&mut self, prompt: &str) -> Result<String, ReadlineError> {
+        match self {
+            Input::Terminal(editor) => editor.readline(prompt),
+            Input::Reader(reader) => {
+                print!("{}", prompt);
+                io::stdout().flush()?;
+
+                let mut line = String::new();
+                match reader.read_line(&mut line)? {
+                    0 => Err(ReadlineError::Eof),
+                    _ => {
+                        println!("{}", line.trim_end());
+                        Ok(line)
+                    }
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::board::GameResult;
+    use crate::game::Game;
+
+    /// Creates a player that reads the given lines.
+    fn reader(lines: &str) -> ConsolePlayer {
+        ConsolePlayer::from_reader(Cursor::new(lines.to_owned()))
+    }
+
+    #[test]
+    fn game_is_played_from_readers() {
+        let game = Game::new(reader("4\n4\n4\n4\n"), reader("3\n3\n3\n"));
+        let (board, result) = game.play();
+
+        assert_eq!(result, GameResult::Win(Token::Player1));
+        assert_eq!(board.to_move_string(), "4343434");
+    }
+
+    #[test]
+    fn illegal_moves_are_read_again() {
+        let board = Board::from_move_string("111111").unwrap();
+        let mut player = reader("0\n1\n8\nx\nr\n2\n");
+
+        assert_eq!(player.read_move(&board, Token::Player1).unwrap(), 1);
+    }
+
+    #[test]
+    fn read_move_returns_end_of_input() {
+        let mut player = reader("");
+
+        let result = player.read_move(&Board::new(), Token::Player1);
+        assert!(matches!(result, Err(ReadlineError::Eof)));
+    }
+
+    #[test]
+    fn end_of_input_quits_the_game() {
+        let game = Game::new(reader("4\n4\n"), reader("3\n"));
+        let (board, result) = game.play();
+
+        assert_eq!(result, GameResult::InProgress);
+        assert_eq!(board.to_move_string(), "434");
+    }
+
+    #[test]
+    fn undo_takes_back_a_move_of_each_player() {
+        let game = Game::new(reader("4\n5\n"), reader("4\nu\n3\n"));
+        let (board, result) = game.play();
+
+        assert_eq!(result, GameResult::InProgress);
+        assert_eq!(board.to_move_string(), "43");
+    }
+
+    #[test]
+    fn undo_without_a_move_asks_again() {
+        let game = Game::new(reader("u\n4\n"), reader("undo\n3\n"));
+        let (board, _) = game.play();
+
+        assert_eq!(board.to_move_string(), "43");
+    }
+
+    #[test]
+    fn end_of_input_records_the_quitter() {
+        let mut game = Game::new(reader("4\n"), reader(""));
+
+        assert_eq!(game.step(), GameResult::InProgress);
+        assert_eq!(game.quitter(), None);
+        assert_eq!(game.step(), GameResult::InProgress);
+        assert_eq!(game.quitter(), Some(Token::Player2));
+        assert_eq!(game.board().ply(), 1);
+    }
+
+    #[test]
+    fn console_player_is_named() {
+        assert_eq!(ConsolePlayer::new().name(), "Console");
+        assert_eq!(reader("").name(), "Console");
+    }
+}
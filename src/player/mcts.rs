@@ -0,0 +1,236 @@
+use std::borrow::Cow;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::board::{Board, Column};
+use crate::player::{Player, PlayerAction, Token};
+
+/// The default exploration constant of the UCT formula.
+pub const DEFAULT_EXPLORATION: f64 = std::f64::consts::SQRT_2;
+
+/// A player that decides its moves with Monte Carlo tree search.
+///
+/// Each iteration walks down the search tree choosing moves with the UCT formula, adds a node
+/// for an untried move, and plays random moves from there to the end of the game. The move
+/// from the root visited the most is played.
+pub struct MctsPlayer {
+    iterations: u32,
+    exploration: f64,
+    rng: StdRng,
+}
+
+impl MctsPlayer {
+    /// Creates a player that runs the given number of iterations for each move.
+    pub fn new(iterations: u32) -> MctsPlayer {
+        MctsPlayer::with_rng(iterations, StdRng::from_entropy())
+    }
+
+    /// Creates a player that runs the given number of iterations for each move, with a random
+    /// number generator seeded by `seed`, so it always makes the same move on the same board.
+    pub fn with_seed(iterations: u32, seed: u64) -> MctsPlayer {
+        MctsPlayer::with_rng(iterations, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(iterations: u32, rng: StdRng) -> MctsPlayer {
+        assert!(iterations > 0, "at least one iteration is required");
+
+        MctsPlayer {
+            iterations,
+            exploration: DEFAULT_EXPLORATION,
+            rng,
+        }
+    }
+
+    /// Sets the exploration constant of the UCT formula, where greater values search less
+    /// promising moves more often.
+    pub fn with_exploration(mut self, exploration: f64) -> MctsPlayer {
+        self.exploration = exploration;
+        self
+    }
+
+    /// Decides the move for `token` to play.
+    pub fn decide_move(&mut self, board: &Board, token: Token) -> Column {
+        // The root is only used for its children, so the move leading to it does not matter.
+        let mut tree = vec![Node::new(board, 0, token.opponent(), None)];
+        for _ in 0..self.iterations {
+            self.iterate(&mut tree, board);
+        }
+
+        tree[0]
+            .children
+            .iter()
+            .map(|&child| &tree[child])
+            .max_by_key(|child| child.visits)
+            .map(|child| child.column)
+            .expect("no legal moves")
+    }
+
+    /// Runs a single iteration of the search from the root of the tree.
+    fn iterate(&mut self, tree: &mut Vec<Node>, board: &Board) {
+        let mut board = *board;
+        let mut node = 0;
+
+        // Select moves down the tree until reaching a node with untried moves.
+        while tree[node].untried.is_empty() && !tree[node].children.is_empty() {
+            node = self.select(tree, node);
+            board.make_move(tree[node].column);
+        }
+
+        // Expand the tree with one of the untried moves.
+        if !tree[node].untried.is_empty() {
+            let index = self.rng.gen_range(0, tree[node].untried.len());
+            let column = tree[node].untried.swap_remove(index);
+            let token = board.current_player();
+            board.make_move(column);
+
+            let child = tree.len();
+            tree.push(Node::new(&board, column, token, Some(node)));
+            tree[node].children.push(child);
+            node = child;
+        }
+
+        let winner = self.rollout(board);
+
+        // Update the nodes on the path back to the root, from the perspective of the player
+        // who made the move to each node.
+        let mut next = Some(node);
+        while let Some(node) = next {
+            let node = &mut tree[node];
+            node.visits += 1;
+            node.wins += match winner {
+                Some(winner) if winner == node.token => 1.0,
+                Some(_) => 0.0,
+                None => 0.5,
+            };
+            next = node.parent;
+        }
+    }
+
+    /// Gets the child of the node with the greatest UCT value.
+    fn select(&self, tree: &[Node], node: usize) -> usize {
+        let log_visits = f64::from(tree[node].visits).ln();
+        let uct = |child: &Node| {
+            let visits = f64::from(child.visits);
+            child.wins / visits + self.exploration * (log_visits / visits).sqrt()
+        };
+
+        let mut children = tree[node].children.iter().copied();
+        let mut best = children.next().expect("node has no children");
+        let mut best_value = uct(&tree[best]);
+        for child in children {
+            let value = uct(&tree[child]);
+            if value > best_value {
+                best = child;
+                best_value = value;
+            }
+        }
+
+        best
+    }
+
+    /// Plays random moves until the game is over, returning the winner if there is one.
+    fn rollout(&mut self, mut board: Board) -> Option<Token> {
+        loop {
+            if let Some(winner) = board.winner() {
+                return Some(winner);
+            }
+            if board.is_draw() {
+                return None;
+            }
+
+            let moves: Vec<Column> = board.legal_moves().collect();
+            board.make_move(moves[self.rng.gen_range(0, moves.len())]);
+        }
+    }
+}
+
+impl Player for MctsPlayer {
+    fn decide_action(&mut self, board: &Board, token: Token) -> PlayerAction {
+        PlayerAction::Move(self.decide_move(board, token))
+    }
+
+    fn name(&self) -> Cow<'_, str> {
+        Cow::Borrowed("MCTS")
+    }
+}
+
+/// A node of the search tree, for the board after a move.
+struct Node {
+    /// The move that led to the node.
+    column: Column,
+    /// The player that made the move.
+    token: Token,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    /// The legal moves from the node that have no child yet.
+    untried: Vec<Column>,
+    visits: u32,
+    /// The number of games won by `token` from the node, with draws counting as half a win.
+    wins: f64,
+}
+
+impl Node {
+    fn new(board: &Board, column: Column, token: Token, parent: Option<usize>) -> Node {
+        let untried = match board.winner() {
+            Some(_) => Vec::new(),
+            None => board.legal_moves().collect(),
+        };
+
+        Node {
+            column,
+            token,
+            parent,
+            children: Vec::new(),
+            untried,
+            visits: 0,
+            wins: 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::WIDTH;
+
+    #[test]
+    fn empty_board_is_opened_near_the_center() {
+        let board = Board::new();
+
+        for seed in 0..5 {
+            let mut player = MctsPlayer::with_seed(5000, seed);
+            let column = player.decide_move(&board, Token::Player1);
+            assert!(board.is_legal(column));
+            // One of the three central columns, which are part of the most lines.
+            assert!(
+                (WIDTH / 2 - 1..=WIDTH / 2 + 1).contains(&column),
+                "{}",
+                column
+            );
+        }
+    }
+
+    #[test]
+    fn win_in_one_is_found() {
+        let board = Board::from_move_string("121212").unwrap();
+
+        for seed in 0..5 {
+            let mut player = MctsPlayer::with_seed(1000, seed);
+            assert_eq!(player.decide_move(&board, Token::Player1), 0);
+        }
+    }
+
+    #[test]
+    fn seeded_players_make_the_same_moves() {
+        let board = Board::from_move_string("4453").unwrap();
+        let mut a = MctsPlayer::with_seed(200, 9);
+        let mut b = MctsPlayer::with_seed(200, 9);
+
+        for _ in 0..3 {
+            let column = a.decide_move(&board, Token::Player1);
+            assert!(board.is_legal(column));
+            assert_eq!(b.decide_move(&board, Token::Player1), column);
+        }
+    }
+}
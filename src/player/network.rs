@@ -0,0 +1,161 @@
+use std::borrow::Cow;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+
+use crate::board::{Board, Column};
+use crate::player::{Player, PlayerAction, Token};
+
+/// A player on the other end of a TCP connection.
+///
+/// Each time a move is needed, the moves played so far are sent as a line in the format of
+/// [`Board::to_notation`], and a line with the column to play, counting from 1, is read back.
+/// If the column is not a legal move, the moves are sent again.
+///
+/// ```text
+/// > 4453
+/// < 8
+/// > 4453
+/// < 3
+/// ```
+pub struct TcpPlayer {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    error: Option<io::Error>,
+}
+
+impl TcpPlayer {
+    /// Creates a player that plays over the connection.
+    pub fn new(stream: TcpStream) -> io::Result<TcpPlayer> {
+        Ok(TcpPlayer {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+            error: None,
+        })
+    }
+
+    /// Connects to a player listening at the address.
+    pub fn connect(addr: impl ToSocketAddrs) -> io::Result<TcpPlayer> {
+        TcpPlayer::new(TcpStream::connect(addr)?)
+    }
+
+    /// Gets the error that made the player quit, if the connection failed.
+    pub fn last_error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    /// Asks the remote player for moves until a legal move is given.
+    ///
+    /// Returns an error if the connection fails, of kind [`io::ErrorKind::UnexpectedEof`] if the
+    /// remote player closed it without replying.
+    pub fn read_move(&mut self, board: &Board) -> io::Result<Column> {
+        let moves = board.to_notation();
+
+        loop {
+            writeln!(self.writer, "{}", moves)?;
+            self.writer.flush()?;
+
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "remote player disconnected",
+                ));
+            }
+
+            match Column::from_str(line.trim()) {
+                Ok(column) if column > 0 && board.is_legal(column - 1) => return Ok(column - 1),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Player for TcpPlayer {
+    /// Quits the game if the connection fails, keeping the error in [`TcpPlayer::last_error`].
+    fn decide_action(&mut self, board: &Board, _token: Token) -> PlayerAction {
+        match self.read_move(board) {
+            Ok(column) => PlayerAction::Move(column),
+            Err(err) => {
+                self.error = Some(err);
+                PlayerAction::Quit
+            }
+        }
+    }
+
+    fn name(&self) -> Cow<'_, str> {
+        Cow::Borrowed("Network")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::*;
+
+    /// Starts a remote player that replies to each line it is sent with the next of `replies`,
+    /// and disconnects once they run out, returning a player connected to it and a handle to the
+    /// lines the remote player was sent.
+    fn remote(replies: &'static [&'static str]) -> (TcpPlayer, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = stream;
+
+            let mut received = Vec::new();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 {
+                received.push(line.trim_end().to_owned());
+                line.clear();
+
+                match replies.get(received.len() - 1) {
+                    Some(reply) => writeln!(writer, "{}", reply).unwrap(),
+                    None => break,
+                }
+            }
+            received
+        });
+
+        (TcpPlayer::connect(addr).unwrap(), handle)
+    }
+
+    #[test]
+    fn illegal_replies_are_asked_again() {
+        let board = Board::from_notation("4453").unwrap();
+        let (mut player, remote) = remote(&["8", "0", "x", "3"]);
+
+        assert_eq!(player.read_move(&board).unwrap(), 2);
+        drop(player);
+        assert_eq!(remote.join().unwrap(), ["4453"; 4]);
+    }
+
+    #[test]
+    fn disconnect_is_an_error() {
+        let board = Board::new();
+        let (mut player, remote) = remote(&[]);
+
+        let err = player.read_move(&board).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(remote.join().unwrap(), [""]);
+    }
+
+    #[test]
+    fn disconnect_quits_the_game() {
+        let board = Board::new();
+        let (mut player, remote) = remote(&[]);
+        assert!(player.last_error().is_none());
+
+        assert_eq!(
+            player.decide_action(&board, Token::Player1),
+            PlayerAction::Quit
+        );
+        let err = player.last_error().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        remote.join().unwrap();
+    }
+}
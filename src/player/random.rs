@@ -0,0 +1,82 @@
+use std::borrow::Cow;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::board::{Board, Column};
+use crate::player::{Player, PlayerAction, Token};
+
+/// A player that makes a random legal move, each with the same chance.
+pub struct RandomPlayer {
+    rng: StdRng,
+}
+
+impl RandomPlayer {
+    pub fn new() -> RandomPlayer {
+        RandomPlayer {
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Creates a player with a random number generator seeded by `seed`, so it always makes the
+    /// same moves in the same game.
+    pub fn with_seed(seed: u64) -> RandomPlayer {
+        RandomPlayer {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Decides a random legal move to play on the board.
+    pub fn decide_move(&mut self, board: &Board) -> Column {
+        let moves: Vec<Column> = board.legal_moves().collect();
+        assert!(!moves.is_empty(), "no legal moves");
+
+        moves[self.rng.gen_range(0, moves.len())]
+    }
+}
+
+impl Default for RandomPlayer {
+    fn default() -> RandomPlayer {
+        RandomPlayer::new()
+    }
+}
+
+impl Player for RandomPlayer {
+    fn decide_action(&mut self, board: &Board, _token: Token) -> PlayerAction {
+        PlayerAction::Move(self.decide_move(board))
+    }
+
+    fn name(&self) -> Cow<'_, str> {
+        Cow::Borrowed("Random")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_legal_moves_are_made() {
+        // The first column is full.
+        let board = Board::from_move_string("111111").unwrap();
+        let mut player = RandomPlayer::with_seed(3);
+
+        let mut columns: Vec<Column> = (0..200).map(|_| player.decide_move(&board)).collect();
+        columns.sort_unstable();
+        columns.dedup();
+        assert_eq!(columns, [1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn seeded_players_make_the_same_moves() {
+        let mut a = RandomPlayer::with_seed(11);
+        let mut b = RandomPlayer::with_seed(11);
+
+        let mut board = Board::new();
+        while !board.result().is_over() {
+            let column = a.decide_move(&board);
+            assert_eq!(b.decide_move(&board), column);
+            board.make_move(column);
+        }
+    }
+}
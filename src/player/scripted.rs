@@ -0,0 +1,92 @@
+use std::borrow::Cow;
+use std::vec;
+
+use crate::board::{Board, Column};
+use crate::player::{Player, PlayerAction, Token};
+
+/// A player that makes a fixed list of moves in order.
+///
+/// The player panics if it is asked for a move once the list has run out, or if the next move
+/// in the list is illegal.
+pub struct ScriptedPlayer {
+    moves: vec::IntoIter<Column>,
+}
+
+impl ScriptedPlayer {
+    /// Creates a player that makes the given moves, in order.
+    pub fn new(moves: Vec<Column>) -> ScriptedPlayer {
+        ScriptedPlayer {
+            moves: moves.into_iter(),
+        }
+    }
+
+    /// Gets the moves the player has left to make.
+    pub fn remaining(&self) -> &[Column] {
+        self.moves.as_slice()
+    }
+
+    /// Makes the next move for `token` in the list.
+    pub fn decide_move(&mut self, board: &Board, token: Token) -> Column {
+        let column = self
+            .moves
+            .next()
+            .unwrap_or_else(|| panic!("script for {} ran out of moves", token));
+        assert!(board.is_legal(column), "illegal scripted move: {}", column);
+
+        column
+    }
+}
+
+impl Player for ScriptedPlayer {
+    fn decide_action(&mut self, board: &Board, token: Token) -> PlayerAction {
+        PlayerAction::Move(self.decide_move(board, token))
+    }
+
+    fn name(&self) -> Cow<'_, str> {
+        Cow::Borrowed("Scripted")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves_are_made_in_order() {
+        let mut player = ScriptedPlayer::new(vec![3, 3, 4]);
+        let mut board = Board::new();
+
+        for &expected in &[3, 3, 4] {
+            let token = board.current_player();
+            assert_eq!(
+                player.decide_action(&board, token),
+                PlayerAction::Move(expected)
+            );
+            board.make_move(expected);
+        }
+        assert!(player.remaining().is_empty());
+    }
+
+    #[test]
+    fn remaining_moves_are_kept() {
+        let mut player = ScriptedPlayer::new(vec![0, 1, 2]);
+        player.decide_move(&Board::new(), Token::Player1);
+
+        assert_eq!(player.remaining(), [1, 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "script for o ran out of moves")]
+    fn running_out_of_moves_panics() {
+        let mut player = ScriptedPlayer::new(Vec::new());
+        player.decide_move(&Board::new(), Token::Player2);
+    }
+
+    #[test]
+    #[should_panic(expected = "illegal scripted move: 0")]
+    fn illegal_moves_panic() {
+        let board = Board::from_move_string("111111").unwrap();
+        let mut player = ScriptedPlayer::new(vec![0]);
+        player.decide_move(&board, Token::Player1);
+    }
+}
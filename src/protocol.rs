@@ -0,0 +1,202 @@
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::board::Board;
+use crate::player::ai::{AIPlayer, Difficulty, SearchOptions, SearchStats};
+
+/// Runs the AI as an engine driven by text commands, in the style of the UCI protocol for chess
+/// engines, until the input ends or the `quit` command is given.
+///
+/// The commands are read from `input` one per line:
+///
+/// - `position <moves>` sets the board to the moves played from the empty board, in the format
+///   of [`Board::from_notation`]. Without moves, the board is empty.
+/// - `go depth <n>` searches the board to the depth of `n` plies, `go movetime <ms>` searches
+///   for `ms` milliseconds and `go` alone searches like the hard difficulty.
+/// - `stop` stops the search as soon as possible, playing the best move found so far.
+/// - `isready` is answered with `readyok` once every command before it is done.
+/// - `quit` stops the engine.
+///
+/// After each completed iteration of a search an `info depth <n> nodes <n> time <ms>` line is
+/// written to `output`, followed by `bestmove <column>` at the end of the search, with the
+/// column counting from 1, or `bestmove none` if the game is over. Invalid commands are answered
+/// with an `info string` line describing the problem.
+pub fn run(input: impl BufRead + Send + 'static, mut output: impl Write) -> io::Result<()> {
+    let (sender, receiver) = mpsc::channel();
+
+    // Commands are read on another thread, so a search can be stopped while it runs. Each search
+    // has a flag of its own, so a `stop` only applies to the searches before it. The thread is
+    // left blocked on the input after a `quit`, rather than waiting for the input to end.
+    let reader = thread::spawn(move || -> io::Result<()> {
+        let mut cancel = Arc::new(AtomicBool::new(false));
+        for line in input.lines() {
+            let line = line?;
+            let command = line.split_whitespace().next();
+
+            if command == Some("stop") {
+                cancel.store(true, Ordering::Relaxed);
+            } else if command == Some("go") {
+                cancel = Arc::new(AtomicBool::new(false));
+            }
+
+            if sender.send((line, Arc::clone(&cancel))).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    });
+
+    let mut board = Board::new();
+    for (line, cancel) in receiver {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("position") => match Board::from_notation(words.next().unwrap_or("")) {
+                Ok(position) => board = position,
+                Err(err) => writeln!(output, "info string invalid position: {}", err)?,
+            },
+            Some("go") => match parse_go(words) {
+                Some(mut ai) => go(&mut ai, &board, &cancel, &mut output)?,
+                None => writeln!(output, "info string invalid go command: {}", line)?,
+            },
+            Some("isready") => writeln!(output, "readyok")?,
+            Some("quit") => return Ok(()),
+            // Nothing is searching by the time a `stop` is handled.
+            Some("stop") | None => {}
+            Some(command) => writeln!(output, "info string unknown command: {}", command)?,
+        }
+        output.flush()?;
+    }
+
+    // The commands ran out, so the reader has finished.
+    reader.join().expect("command reader panicked")
+}
+
+/// Creates the player to search with from the arguments of a `go` command.
+fn parse_go<'a>(mut args: impl Iterator<Item = &'a str>) -> Option<AIPlayer> {
+    let ai = match (args.next(), args.next()) {
+        (None, _) => AIPlayer::new(Difficulty::Hard),
+        (Some("depth"), Some(depth)) => AIPlayer::with_depth(depth.parse().ok()?),
+        (Some("movetime"), Some(ms)) => AIPlayer::new(Difficulty::Hard)
+            .with_time_budget(Duration::from_millis(ms.parse().ok()?)),
+        _ => return None,
+    };
+
+    match args.next() {
+        Some(_) => None,
+        None => Some(ai),
+    }
+}
+
+/// Searches the board, writing the progress of the search and the best move to `output`.
+fn go(
+    ai: &mut AIPlayer,
+    board: &Board,
+    cancel: &AtomicBool,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    if board.result().is_over() {
+        return writeln!(output, "bestmove none");
+    }
+
+    let mut result: io::Result<()> = Ok(());
+    let mut progress = |stats: &SearchStats| {
+        if result.is_ok() {
+            result = writeln!(
+                output,
+                "info depth {} nodes {} time {}",
+                stats.depth,
+                stats.nodes,
+                stats.elapsed.as_millis()
+            )
+            .and_then(|()| output.flush());
+        }
+    };
+    let options = SearchOptions {
+        cancel: Some(cancel),
+        progress: Some(&mut progress),
+    };
+    let column = ai.decide_move_with(board, board.current_player(), options);
+    result?;
+
+    writeln!(output, "bestmove {}", column + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read};
+
+    use super::*;
+    use crate::board::Column;
+
+    /// Runs the commands, returning the lines written in response.
+    fn run_commands(commands: &str) -> Vec<String> {
+        let mut output = Vec::new();
+        run(Cursor::new(commands.to_owned()), &mut output).unwrap();
+
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(str::to_owned)
+            .collect()
+    }
+
+    #[test]
+    fn go_depth_writes_a_legal_bestmove() {
+        let lines = run_commands("position 4453\ngo depth 5\n");
+
+        let (last, info) = lines.split_last().unwrap();
+        assert_eq!(info.len(), 5);
+        for (depth, line) in (1..).zip(info) {
+            assert!(line.starts_with(&format!("info depth {} nodes ", depth)));
+        }
+
+        let column: Column = last.strip_prefix("bestmove ").unwrap().parse().unwrap();
+        let board = Board::from_notation("4453").unwrap();
+        assert!(column > 0 && board.is_legal(column - 1));
+    }
+
+    #[test]
+    fn go_on_a_finished_game_has_no_bestmove() {
+        assert_eq!(
+            run_commands("position 1212121\ngo depth 3\n"),
+            ["bestmove none"]
+        );
+    }
+
+    #[test]
+    fn invalid_commands_are_reported() {
+        let lines = run_commands("position 8\ngo depth x\nisready\nfoo\n");
+
+        assert!(lines[0].starts_with("info string invalid position: "));
+        assert_eq!(lines[1], "info string invalid go command: go depth x");
+        assert_eq!(lines[2], "readyok");
+        assert_eq!(lines[3], "info string unknown command: foo");
+    }
+
+    /// Input that never ends once its data has been read, like a pipe that is left open.
+    struct Open(Cursor<&'static str>);
+
+    impl Read for Open {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.read(buf)? {
+                0 => loop {
+                    thread::park();
+                },
+                len => Ok(len),
+            }
+        }
+    }
+
+    #[test]
+    fn quit_returns_while_the_input_is_open() {
+        let input = io::BufReader::new(Open(Cursor::new("isready\nquit\n")));
+
+        let mut output = Vec::new();
+        run(input, &mut output).unwrap();
+        assert_eq!(output, b"readyok\n");
+    }
+}
@@ -0,0 +1,115 @@
+use crate::board::{Board, Column, MoveStringError};
+
+/// A cursor over the boards of a recorded game, which can step forwards and backwards through
+/// the moves of the game.
+///
+/// The replay starts at the empty board, before the first move.
+#[derive(Clone)]
+pub struct Replay {
+    /// The moves of the game.
+    moves: Vec<Column>,
+    /// The board after the moves up to the cursor.
+    board: Board,
+}
+
+impl Replay {
+    /// Creates a replay of the moves played on the board.
+    pub fn new(board: &Board) -> Replay {
+        Replay {
+            moves: board.move_history().to_vec(),
+            board: Board::new(),
+        }
+    }
+
+    /// Creates a replay from a string of moves, in the format of [`Board::from_move_string`].
+    pub fn from_move_string(moves: &str) -> Result<Replay, MoveStringError> {
+        Board::from_move_string(moves).map(|board| Replay::new(&board))
+    }
+
+    /// Gets the board at the cursor.
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Gets the number of moves played before the cursor.
+    pub fn ply(&self) -> usize {
+        self.board.ply()
+    }
+
+    /// Gets the number of moves in the game.
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+
+    /// Gets whether the game has no moves.
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+    }
+
+    /// Moves the cursor to after the given number of moves, returning the board there.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ply` is greater than the number of moves in the game.
+    pub fn goto(&mut self, ply: usize) -> &Board {
+        assert!(ply <= self.len(), "ply {} is past the end of the game", ply);
+
+        self.board = Board::from_moves(&self.moves[..ply]).expect("recorded moves are legal");
+        &self.board
+    }
+
+    /// Plays the next move, returning the board after it, or `None` at the end of the game.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<&Board> {
+        let column = *self.moves.get(self.ply())?;
+        self.board.make_move(column);
+        Some(&self.board)
+    }
+
+    /// Takes back the previous move, returning the board before it, or `None` at the start of
+    /// the game.
+    pub fn prev(&mut self) -> Option<&Board> {
+        if self.ply() == 0 {
+            return None;
+        }
+
+        self.board.undo_move();
+        Some(&self.board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_steps_through_the_game() {
+        let mut replay = Replay::from_move_string("4453").unwrap();
+        assert_eq!(replay.len(), 4);
+        assert_eq!(replay.ply(), 0);
+        assert!(replay.prev().is_none());
+
+        assert_eq!(replay.next().unwrap().move_history(), [3]);
+        assert_eq!(replay.next().unwrap().move_history(), [3, 3]);
+        assert_eq!(replay.prev().unwrap().move_history(), [3]);
+
+        assert_eq!(replay.goto(4).move_history(), [3, 3, 4, 2]);
+        assert!(replay.next().is_none());
+        assert_eq!(replay.goto(0).ply(), 0);
+    }
+
+    #[test]
+    fn empty_replay_has_no_moves() {
+        let mut replay = Replay::new(&Board::new());
+
+        assert!(replay.is_empty());
+        assert!(replay.next().is_none());
+        assert!(replay.prev().is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "ply 5 is past the end of the game")]
+    fn goto_past_the_end_panics() {
+        Replay::from_move_string("4453").unwrap().goto(5);
+    }
+}
@@ -0,0 +1,175 @@
+use std::fmt;
+
+use crate::board::{Board, GameResult};
+use crate::game::Game;
+use crate::player::ai::eval::Evaluator;
+use crate::player::ai::AIPlayer;
+use crate::player::{Player, PlayerAction, Token};
+
+/// The results of a series of games between two AI players, from the perspective of the first.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Tally {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    /// The moves made by the first player.
+    pub first: Record,
+    /// The moves made by the second player.
+    pub second: Record,
+}
+
+impl Tally {
+    /// Gets the number of games played.
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+}
+
+impl fmt::Display for Tally {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "+{} ={} -{} ({:.0} vs {:.0} nodes/move)",
+            self.wins,
+            self.draws,
+            self.losses,
+            self.first.average_nodes(),
+            self.second.average_nodes(),
+        )
+    }
+}
+
+/// The moves made by one of the players over a series of games.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Record {
+    /// The number of moves made.
+    pub moves: u64,
+    /// The number of positions searched for the moves.
+    pub nodes: u64,
+}
+
+impl Record {
+    /// Gets the average number of positions searched for a move.
+    pub fn average_nodes(&self) -> f64 {
+        match self.moves {
+            0 => 0.0,
+            moves => self.nodes as f64 / moves as f64,
+        }
+    }
+}
+
+/// Plays `games` games between two AI players, returning the results.
+///
+/// The players are created for each game by `first` and `second`, which are given the index of
+/// the game, so each game starts with empty transposition tables. Creating the players with
+/// [`AIPlayer::with_seed`] makes the games repeatable. The first player moves first in even
+/// games and second in odd games.
+pub fn play<E, F>(
+    games: u32,
+    mut first: impl FnMut(u32) -> AIPlayer<E>,
+    mut second: impl FnMut(u32) -> AIPlayer<F>,
+) -> Tally
+where
+    E: Evaluator,
+    F: Evaluator,
+{
+    let mut tally = Tally::default();
+
+    for game in 0..games {
+        let mut first = Recorded {
+            player: first(game),
+            record: &mut tally.first,
+        };
+        let mut second = Recorded {
+            player: second(game),
+            record: &mut tally.second,
+        };
+
+        let (first_token, result) = if game % 2 == 0 {
+            (Token::Player1, Game::new(&mut first, &mut second).play().1)
+        } else {
+            (Token::Player2, Game::new(&mut second, &mut first).play().1)
+        };
+
+        match result {
+            GameResult::Win(token) if token == first_token => tally.wins += 1,
+            GameResult::Win(_) => tally.losses += 1,
+            _ => tally.draws += 1,
+        }
+    }
+
+    tally
+}
+
+/// An AI player that records the moves it makes.
+struct Recorded<'a, E: Evaluator> {
+    player: AIPlayer<E>,
+    record: &'a mut Record,
+}
+
+impl<E: Evaluator> Player for &mut Recorded<'_, E> {
+    fn decide_action(&mut self, board: &Board, token: Token) -> PlayerAction {
+        let column = self.player.decide_move(board, token);
+        self.record.moves += 1;
+        self.record.nodes += self.player.last_stats().nodes;
+        PlayerAction::Move(column)
+    }
+
+    fn new_game(&mut self) {
+        self.player.new_game();
+    }
+
+    fn game_over(&mut self, board: &Board, result: GameResult) {
+        self.player.game_over(board, result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::ai::Difficulty;
+
+    #[test]
+    fn seeded_games_are_repeatable() {
+        let play = || {
+            play(
+                4,
+                |game| AIPlayer::with_seed(Difficulty::Easy, game.into()),
+                |game| AIPlayer::with_seed(Difficulty::Easy, 100 + u64::from(game)),
+            )
+        };
+
+        let tally = play();
+        assert_eq!(tally.games(), 4);
+        assert!(tally.first.moves > 0 && tally.second.moves > 0);
+        assert!(tally.first.nodes > 0 && tally.second.nodes > 0);
+        assert_eq!(play(), tally);
+    }
+
+    #[test]
+    fn stronger_player_wins_more() {
+        let tally = play(
+            4,
+            |game| AIPlayer::with_seed(Difficulty::Medium, game.into()),
+            |game| AIPlayer::with_seed(Difficulty::Easy, game.into()).with_blunder_rate(0.5),
+        );
+
+        assert!(tally.wins > tally.losses, "{}", tally);
+    }
+
+    #[test]
+    fn tally_is_displayed_with_the_average_nodes() {
+        let tally = Tally {
+            wins: 3,
+            draws: 1,
+            losses: 2,
+            first: Record {
+                moves: 4,
+                nodes: 12,
+            },
+            second: Record::default(),
+        };
+
+        assert_eq!(tally.to_string(), "+3 =1 -2 (3 vs 0 nodes/move)");
+    }
+}
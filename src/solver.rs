@@ -0,0 +1,254 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::board::{BitBoard, Board, BOARD_SIZE};
+
+/// The game-theoretic score of a position.
+///
+/// A positive score means the side to move can force a win, a negative score means it will lose
+/// against perfect play, and zero is a draw. The magnitude is the number of tokens the winner has
+/// left to play when the game ends: `(BOARD_SIZE + 1 - moves) / 2`, where `moves` is the number
+/// of tokens on the board before the winning move. Faster wins therefore score higher.
+pub type Score = i32;
+
+/// The outcome of a game with perfect play, relative to the side to move.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Outcome {
+    Win,
+    Draw,
+    Loss,
+}
+
+/// A perfect-play solver that searches to the end of the game.
+///
+/// The solver keeps its transposition table between calls to [`Solver::solve`], so reusing it
+/// for related positions is much faster than solving each from scratch.
+pub struct Solver {
+    /// Upper bounds of position scores, keyed by position code.
+    ttable: HashMap<BitBoard, Score>,
+}
+
+impl Solver {
+    /// Creates a new solver with an empty transposition table.
+    pub fn new() -> Solver {
+        Solver {
+            ttable: HashMap::new(),
+        }
+    }
+
+    /// Solves the given board, returning its exact score for the side to move.
+    ///
+    /// If the game has already been won, the side to move has lost and the score reflects the
+    /// move that won it. Early positions take a very long time to solve.
+    pub fn solve(&mut self, board: &Board) -> Score {
+        self.search(board, false)
+    }
+
+    /// Solves whether the side to move wins, draws or loses on the given board.
+    ///
+    /// This is much faster than [`Solver::solve`], since it does not need to find the distance
+    /// to the end of the game.
+    pub fn wdl(&mut self, board: &Board) -> Outcome {
+        match self.search(board, true).cmp(&0) {
+            Ordering::Greater => Outcome::Win,
+            Ordering::Equal => Outcome::Draw,
+            Ordering::Less => Outcome::Loss,
+        }
+    }
+
+    /// Finds the number of plies within `max_plies` in which the side to move can force a win,
+    /// counting the winning move, or `None` if it cannot force a win that soon.
+    ///
+    /// This only narrows down scores of wins within the limit, so it is much faster than
+    /// [`Solver::solve`] for small limits.
+    pub fn mate_in(&mut self, board: &Board, max_plies: usize) -> Option<usize> {
+        let moves = board.ply();
+
+        if max_plies == 0 || board.winner().is_some() || board.is_full() {
+            return None;
+        }
+        if board.winning_move().is_some() {
+            return Some(1);
+        }
+
+        // The most tokens the board can hold when the winning move is played within the limit,
+        // on the turn of the side to move.
+        let mut last = (moves + max_plies - 1).min(BOARD_SIZE as usize - 1);
+        last -= (last - moves) & 1;
+
+        let threshold = (BOARD_SIZE as Score + 1 - last as Score) / 2;
+        let max = (BOARD_SIZE as Score + 1 - moves as Score) / 2;
+        let mut board = *board;
+        let score = self.narrow(&mut board, threshold - 1, max);
+        if score < threshold {
+            return None;
+        }
+
+        let mut winning_moves = BOARD_SIZE as usize + 1 - 2 * score as usize;
+        winning_moves -= (winning_moves - moves) & 1;
+        Some(winning_moves - moves + 1)
+    }
+
+    /// Clears the transposition table.
+    pub fn reset(&mut self) {
+        self.ttable.clear();
+    }
+
+    /// Searches the board for its score.
+    ///
+    /// If `weak` is set, only the sign of the score is exact.
+    fn search(&mut self, board: &Board, weak: bool) -> Score {
+        let mut board = *board;
+        let moves = board.ply() as Score;
+
+        if board.winner().is_some() {
+            return -(BOARD_SIZE as Score + 2 - moves) / 2;
+        }
+        if board.winning_move().is_some() {
+            return (BOARD_SIZE as Score + 1 - moves) / 2;
+        }
+
+        let (min, max) = if weak {
+            (-1, 1)
+        } else {
+            (
+                -(BOARD_SIZE as Score - moves) / 2,
+                (BOARD_SIZE as Score + 1 - moves) / 2,
+            )
+        };
+
+        self.narrow(&mut board, min, max)
+    }
+
+    /// Narrows down the score of the board between `min` and `max`, returning the exact score
+    /// if it is within them, and otherwise the bound it is beyond.
+    ///
+    /// The side to move must not be able to win immediately.
+    fn narrow(&mut self, board: &mut Board, mut min: Score, mut max: Score) -> Score {
+        // Narrow the score down with null window searches, which prune far more than a search
+        // over the full window would.
+        while min < max {
+            let mut med = min + (max - min) / 2;
+            if med <= 0 && min / 2 < med {
+                med = min / 2;
+            } else if med >= 0 && max / 2 > med {
+                med = max / 2;
+            }
+
+            let value = self.negamax(board, med, med + 1);
+            if value <= med {
+                max = value;
+            } else {
+                min = value;
+            }
+        }
+
+        min
+    }
+
+    /// Searches the board to the end of the game within the window `(a, b)`.
+    ///
+    /// The side to move must not be able to win immediately.
+    fn negamax(&mut self, board: &mut Board, mut a: Score, mut b: Score) -> Score {
+        let moves = board.ply() as Score;
+
+        // The previous player could not have won, so if the board is full it is a draw.
+        if moves == BOARD_SIZE as Score {
+            return 0;
+        }
+
+        if board.winning_move().is_some() {
+            return (BOARD_SIZE as Score + 1 - moves) / 2;
+        }
+
+        // The score cannot be higher than winning with the next move after the opponent's reply.
+        let mut max = (BOARD_SIZE as Score - 1 - moves) / 2;
+        if let Some(&upper_bound) = self.ttable.get(&board.position_code()) {
+            max = upper_bound;
+        }
+
+        if b > max {
+            b = max;
+            if a >= b {
+                return b;
+            }
+        }
+
+        for column in board.ordered_moves() {
+            board.make_move(column);
+            let value = -self.negamax(board, -b, -a);
+            board.undo_move();
+
+            if value >= b {
+                return value;
+            }
+            a = a.max(value);
+        }
+
+        self.ttable.insert(board.position_code(), a);
+        a
+    }
+}
+
+impl Default for Solver {
+    fn default() -> Solver {
+        Solver::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Positions near the end of the game with their scores, checked by searching every move to
+    /// the end of the game.
+    const POSITIONS: [(&str, Score); 7] = [
+        ("2252576253462244111563365343671351441", -1),
+        ("7422341735647741166133573473242566", 1),
+        ("23163416124767223154467471272416755633", 0),
+        ("65214673556155731566316327373221417", -1),
+        ("3723651443715621542266323342", 6),
+        ("1376335531756721622714667275", 0),
+        ("33775246523646657744771454652", -2),
+    ];
+
+    #[test]
+    fn solves_positions_near_the_end() {
+        let mut solver = Solver::new();
+        for &(moves, score) in POSITIONS.iter() {
+            let board = Board::from_move_string(moves).unwrap();
+            assert_eq!(solver.solve(&board), score, "moves {:?}", moves);
+        }
+    }
+
+    #[test]
+    fn solves_a_fast_win() {
+        // The first player threatens both ends of three in a row with their next move, winning
+        // with their fourth token.
+        let board = Board::from_move_string("4455").unwrap();
+        assert_eq!(Solver::new().solve(&board), 18);
+    }
+
+    #[test]
+    fn won_board_is_lost_by_the_side_to_move() {
+        let board = Board::from_move_string("1212121").unwrap();
+        assert_eq!(Solver::new().solve(&board), -18);
+    }
+
+    #[test]
+    fn wdl_matches_the_sign_of_the_score() {
+        let mut solver = Solver::new();
+        for &(moves, score) in POSITIONS.iter() {
+            let board = Board::from_move_string(moves).unwrap();
+            let outcome = match score.cmp(&0) {
+                Ordering::Greater => Outcome::Win,
+                Ordering::Equal => Outcome::Draw,
+                Ordering::Less => Outcome::Loss,
+            };
+            assert_eq!(solver.wdl(&board), outcome, "moves {:?}", moves);
+        }
+
+        let won = Board::from_move_string("1212121").unwrap();
+        assert_eq!(solver.wdl(&won), Outcome::Loss);
+    }
+}